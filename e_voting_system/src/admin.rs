@@ -18,25 +18,62 @@
 // - View and audit election results
 // - Coordinate with district officials
 
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use chrono::Utc;
-use crate::models::Election;
-use crate::auth::{hash_password};
+use std::collections::HashMap;
+use crate::models::{
+    AdminAccount, AdminSession, District, PollBookEntry, PollingStation, Position, BallotMethod, Lockout, BallotRules, ElectionStatus, PassThreshold, Permission, Role, Voter, WriteIn, WriteInStatus,
+};
+use crate::audit::{self, AuditEntry};
+use crate::config;
+use crate::district;
+use crate::lockout;
+use crate::auth;
+use crate::election;
+use crate::error::{AppError, AppResult};
+use crate::station::{self, StationReport};
+use crate::tally;
+use crate::token;
+use crate::voter::new_voter_number;
+use crate::vote::{
+    has_voted, list_candidates, list_positions, list_questions, list_write_ins, question_tallies, random_id, ranked_ballots,
+    normalize_code, registered_voter_count, scored_ballots,
+};
 
+/// Admin operations on behalf of a logged-in admin. Only constructed from a
+/// validated session (see `auth::validate_session`).
 pub struct AdminService<'a> {
     conn: &'a Connection,
+    session: &'a AdminSession,
 }
 
 impl<'a> AdminService<'a> {
-    pub fn new(conn: &'a Connection) -> Self {
-        Self { conn }
+    pub fn new(conn: &'a Connection, session: &'a AdminSession) -> Self {
+        Self { conn, session }
+    }
+
+    /// Refuse unless one of the admin's roles grants `permission`. Refusals
+    /// are audited too.
+    fn require(&self, permission: Permission) -> AppResult<()> {
+        let roles = auth::roles_of(self.conn, self.session.admin_id)?;
+        if roles.iter().any(|r| r.allows(permission)) {
+            return Ok(());
+        }
+        self.audit("permission_denied", &format!("needed: {permission}"))?;
+        Err(AppError::PermissionDenied { username: self.session.username.clone(), permission })
+    }
+
+    /// Append an entry for an action performed through this service.
+    fn audit(&self, action: &str, detail: &str) -> rusqlite::Result<()> {
+        audit::record(self.conn, &self.session.username, action, detail)
     }
 
     // ------------------ Election Management ------------------
-    pub fn create_election(&self, name: &str, positions: &[&str]) -> rusqlite::Result<i64> {
+    pub fn create_election(&self, name: &str, positions: &[&str]) -> AppResult<i64> {
+        self.require(Permission::ManageElections)?;
         self.conn.execute(
             "INSERT INTO elections(name, status, created_at) VALUES(?,?,?)",
-            params![name, "Draft", Utc::now().to_rfc3339()])?;
+            params![name, ElectionStatus::Draft, Utc::now().to_rfc3339()])?;
         let eid = self.conn.last_insert_rowid();
 
         for (idx, title) in positions.iter().enumerate() {
@@ -44,85 +81,1188 @@ impl<'a> AdminService<'a> {
                 "INSERT INTO positions(election_id, idx, title) VALUES(?,?,?)",
                 params![eid, idx as i32, title])?;
         }
+        self.audit("create_election", &format!("election #{eid} '{name}' with positions {}", positions.join(", ")))?;
         Ok(eid)
     }
 
-    pub fn update_election_name(&self, election_id: i64, new_name: &str) -> rusqlite::Result<()> {
+    #[allow(dead_code)]
+    pub fn update_election_name(&self, election_id: i64, new_name: &str) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Draft], "rename election")?;
         self.conn.execute("UPDATE elections SET name=?1 WHERE id=?2", params![new_name, election_id])?;
+        self.audit("rename_election", &format!("election #{election_id} renamed to '{new_name}'"))?;
         Ok(())
     }
 
     #[allow(dead_code)]
-    pub fn delete_election(&self, election_id: i64) -> rusqlite::Result<()> {
+    pub fn delete_election(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
         self.conn.execute("DELETE FROM elections WHERE id=?1", params![election_id])?;
+        self.audit("delete_election", &format!("election #{election_id}"))?;
+        Ok(())
+    }
+
+    /// Choose how a position is marked and counted: the method, how many
+    /// seats it fills (only STV fills more than one) and, for approval and
+    /// score ballots, the selection limits. Only allowed before voting opens.
+    pub fn set_ballot_method(&self, election_id: i64, position_idx: i32, rules: &BallotRules) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Draft], "change ballot method")?;
+        let method = rules.method;
+        if rules.seats == 0 {
+            return Err(AppError::Invalid("a position needs at least one seat".to_string()));
+        }
+        if rules.seats > 1 && method != BallotMethod::Stv {
+            return Err(AppError::Invalid(format!("{method} positions fill a single seat; use STV for {} seats", rules.seats)));
+        }
+        if !method.is_multi_select() && (rules.min_selections > 0 || rules.max_selections > 0) {
+            return Err(AppError::Invalid(format!("selection limits only apply to ranked, approval and score positions, not {method}")));
+        }
+        if rules.max_selections > 0 && rules.min_selections > rules.max_selections {
+            return Err(AppError::Invalid("minimum selections cannot exceed the maximum".to_string()));
+        }
+        if rules.allow_write_in && method != BallotMethod::Plurality {
+            return Err(AppError::Invalid(format!("write-ins are only supported on plurality positions, not {method}")));
+        }
+        if method == BallotMethod::Score && rules.max_score == 0 {
+            return Err(AppError::Invalid("the maximum score must be at least 1".to_string()));
+        }
+        let changed = self.conn.execute(
+            "UPDATE positions SET method=?1, seats=?2, min_selections=?3, max_selections=?4, max_score=?5, allow_write_in=?6 WHERE election_id=?7 AND idx=?8",
+            params![method, rules.seats, rules.min_selections, rules.max_selections, rules.max_score, rules.allow_write_in, election_id, position_idx])?;
+        if changed == 0 {
+            return Err(AppError::NotFound(format!("position {position_idx} in election #{election_id}")));
+        }
+        self.audit("set_ballot_method", &format!(
+            "election #{election_id} position {position_idx}: {method}, {} seat(s), selections {}..{}, max score {}, write-ins {}",
+            rules.seats, rules.min_selections, rules.max_selections, rules.max_score, rules.allow_write_in))?;
+        Ok(())
+    }
+
+    /// Add a ballot question (referendum) to a draft election. `options`
+    /// defaults to Yes/No when empty.
+    pub fn add_question(
+        &self,
+        election_id: i64,
+        text: &str,
+        options: &[&str],
+        threshold: PassThreshold,
+        quorum_percent: u32,
+    ) -> AppResult<i64> {
+        self.require(Permission::ManageElections)?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Draft], "add questions")?;
+        let options: Vec<&str> = if options.is_empty() { vec!["Yes", "No"] } else { options.to_vec() };
+        if options.len() < 2 || options.iter().any(|o| o.is_empty()) {
+            return Err(AppError::Invalid("a question needs at least two non-empty options".to_string()));
+        }
+        if quorum_percent > 100 {
+            return Err(AppError::Invalid("quorum must be a percentage from 0 to 100".to_string()));
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        let idx: i32 = tx.query_row(
+            "SELECT COUNT(*) FROM questions WHERE election_id=?1", params![election_id], |row| row.get(0))?;
+        tx.execute(
+            "INSERT INTO questions(election_id, idx, text, threshold, quorum_percent) VALUES(?,?,?,?,?)",
+            params![election_id, idx, text, threshold, quorum_percent])?;
+        let qid = tx.last_insert_rowid();
+        for (i, label) in options.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO question_options(question_id, idx, label) VALUES(?,?,?)",
+                params![qid, i as i32, label])?;
+        }
+        self.audit("add_question", &format!(
+            "question #{qid} on election #{election_id}: '{text}' [{}], {threshold}, quorum {quorum_percent}%", options.join(", ")))?;
+        tx.commit()?;
+        Ok(qid)
+    }
+
+    // ------------------ Election Lifecycle ------------------
+    pub fn open_election(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::RunElections)?;
+        election::open_election(self.conn, election_id)?;
+        self.audit("open_election", &format!("election #{election_id}"))?;
+        Ok(())
+    }
+
+    pub fn close_election(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::RunElections)?;
+        election::close_election(self.conn, election_id)?;
+        self.audit("close_election", &format!("election #{election_id}"))?;
+        Ok(())
+    }
+
+    pub fn suspend_election(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::RunElections)?;
+        election::suspend_election(self.conn, election_id)?;
+        self.audit("suspend_election", &format!("election #{election_id}"))?;
+        Ok(())
+    }
+
+    pub fn cancel_election(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::RunElections)?;
+        election::cancel_election(self.conn, election_id)?;
+        self.audit("cancel_election", &format!("election #{election_id}"))?;
+        Ok(())
+    }
+
+    /// Certify a closed election. Refused while any trusted offline station
+    /// has not sent its results, since the count would be incomplete.
+    pub fn certify_election(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::CertifyElections)?;
+        let outstanding = station::outstanding(self.conn, election_id)?;
+        if !outstanding.is_empty() {
+            let names: Vec<String> = outstanding.iter().map(|(d, s)| format!("'{s}' in '{d}'")).collect();
+            return Err(AppError::Invalid(format!(
+                "results from offline station(s) {} have not been imported", names.join(", "))));
+        }
+        election::certify_election(self.conn, election_id)?;
+        self.audit("certify_election", &format!("election #{election_id}"))?;
         Ok(())
     }
 
     // ------------------ Candidate Management ------------------
-    pub fn add_candidate(&self, election_id: i64, position_idx: i32, name: &str, party: &str) -> rusqlite::Result<()> {
+    /// Candidates may only be changed while the election is still a draft.
+    fn require_editable_candidate(&self, candidate_id: i64) -> AppResult<()> {
+        let election_id: i64 = self.conn
+            .query_row("SELECT election_id FROM candidates WHERE id=?1", params![candidate_id], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("candidate #{candidate_id}")))?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Draft], "edit candidates")?;
+        Ok(())
+    }
+
+    pub fn add_candidate(&self, election_id: i64, position_idx: i32, name: &str, party: &str) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Draft], "add candidates")?;
         self.conn.execute(
             "INSERT INTO candidates(election_id, position_idx, name, party) VALUES(?,?,?,?)",
             params![election_id, position_idx, name, party])?;
+        let candidate_id = self.conn.last_insert_rowid();
+        self.audit("add_candidate", &format!(
+            "candidate #{candidate_id} '{name}' ({party}) for election #{election_id} position {position_idx}"))?;
         Ok(())
     }
 
     #[allow(dead_code)]
-    pub fn update_candidate(&self, candidate_id: i64, new_name: &str, new_party: &str) -> rusqlite::Result<()> {
+    pub fn update_candidate(&self, candidate_id: i64, new_name: &str, new_party: &str) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
+        self.require_editable_candidate(candidate_id)?;
         self.conn.execute(
             "UPDATE candidates SET name=?1, party=?2 WHERE id=?3",
             params![new_name, new_party, candidate_id])?;
+        self.audit("update_candidate", &format!("candidate #{candidate_id} now '{new_name}' ({new_party})"))?;
         Ok(())
     }
 
     #[allow(dead_code)]
-    pub fn remove_candidate(&self, candidate_id: i64) -> rusqlite::Result<()> {
+    pub fn remove_candidate(&self, candidate_id: i64) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
+        self.require_editable_candidate(candidate_id)?;
         self.conn.execute("DELETE FROM candidates WHERE id=?1", params![candidate_id])?;
+        self.audit("remove_candidate", &format!("candidate #{candidate_id}"))?;
         Ok(())
     }
 
+    // ------------------ Write-in Adjudication ------------------
+    pub fn write_in_queue(&self, election_id: i64) -> AppResult<Vec<WriteIn>> {
+        self.require(Permission::AdjudicateWriteIns)?;
+        Ok(list_write_ins(self.conn, election_id, true)?)
+    }
+
+    /// Count a write-in (and every other pending write-in with the same name
+    /// for the same position) for an existing candidate.
+    pub fn merge_write_in(&self, write_in_id: i64, candidate_id: i64) -> AppResult<usize> {
+        self.require(Permission::AdjudicateWriteIns)?;
+        let tx = self.conn.unchecked_transaction()?;
+        let (election_id, position_idx, _) = Self::pending_write_in(&tx, write_in_id)?;
+        let cand_position: Option<i32> = tx
+            .query_row(
+                "SELECT position_idx FROM candidates WHERE id=?1 AND election_id=?2",
+                params![candidate_id, election_id], |row| row.get(0))
+            .optional()?;
+        if cand_position != Some(position_idx) {
+            return Err(AppError::Invalid(format!(
+                "candidate #{candidate_id} is not standing for position {position_idx} in election #{election_id}")));
+        }
+        let n = Self::resolve_write_ins(&tx, write_in_id, WriteInStatus::Merged, Some(candidate_id))?;
+        self.audit("merge_write_in", &format!("write-in #{write_in_id} and {} match(es) counted for candidate #{candidate_id}", n - 1))?;
+        tx.commit()?;
+        Ok(n)
+    }
+
+    /// Create a new candidate from a write-in and count matching write-ins for them.
+    pub fn accept_write_in(&self, write_in_id: i64, party: &str) -> AppResult<(i64, usize)> {
+        self.require(Permission::AdjudicateWriteIns)?;
+        let tx = self.conn.unchecked_transaction()?;
+        let (election_id, position_idx, name) = Self::pending_write_in(&tx, write_in_id)?;
+        // Bypasses the Draft-only rule of add_candidate: the candidate only
+        // exists because voters wrote them in.
+        tx.execute(
+            "INSERT INTO candidates(election_id, position_idx, name, party) VALUES(?,?,?,?)",
+            params![election_id, position_idx, name.trim(), party])?;
+        let candidate_id = tx.last_insert_rowid();
+        let n = Self::resolve_write_ins(&tx, write_in_id, WriteInStatus::Created, Some(candidate_id))?;
+        self.audit("accept_write_in", &format!(
+            "write-in #{write_in_id} became candidate #{candidate_id} '{}' ({party}); {n} write-in(s) counted", name.trim()))?;
+        tx.commit()?;
+        Ok((candidate_id, n))
+    }
+
+    /// Reject a write-in and every other pending write-in with the same name.
+    pub fn reject_write_in(&self, write_in_id: i64) -> AppResult<usize> {
+        self.require(Permission::AdjudicateWriteIns)?;
+        let tx = self.conn.unchecked_transaction()?;
+        Self::pending_write_in(&tx, write_in_id)?;
+        let n = Self::resolve_write_ins(&tx, write_in_id, WriteInStatus::Rejected, None)?;
+        self.audit("reject_write_in", &format!("write-in #{write_in_id} and {} match(es) rejected", n - 1))?;
+        tx.commit()?;
+        Ok(n)
+    }
+
+    /// Load a pending write-in, checking its election can still be adjudicated.
+    fn pending_write_in(conn: &Connection, write_in_id: i64) -> AppResult<(i64, i32, String)> {
+        let (election_id, position_idx, name, status): (i64, i32, String, WriteInStatus) = conn
+            .query_row(
+                "SELECT election_id, position_idx, name, status FROM write_ins WHERE id=?1",
+                params![write_in_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("write-in #{write_in_id}")))?;
+        if status != WriteInStatus::Pending {
+            return Err(AppError::Invalid(format!("write-in #{write_in_id} was already {status}")));
+        }
+        election::require_status(
+            conn, election_id, &[ElectionStatus::Open, ElectionStatus::Closed], "adjudicate write-ins")?;
+        Ok((election_id, position_idx, name))
+    }
+
+    /// Apply a decision to a write-in and all pending write-ins for the same
+    /// position whose name matches ignoring case and surrounding spaces.
+    /// Accepted write-ins become ordinary vote rows on their ballot.
+    fn resolve_write_ins(conn: &Connection, write_in_id: i64, status: WriteInStatus, candidate_id: Option<i64>) -> AppResult<usize> {
+        let now = Utc::now().to_rfc3339();
+        let mut stmt = conn.prepare(
+            "SELECT w.id, w.ballot_id, w.election_id, w.position_idx
+             FROM write_ins w JOIN write_ins target ON target.id=?1
+             WHERE w.status='Pending' AND w.election_id=target.election_id AND w.position_idx=target.position_idx
+               AND LOWER(TRIM(w.name)) = LOWER(TRIM(target.name))")?;
+        let matches: Vec<(i64, i64, i64, i32)> = stmt
+            .query_map(params![write_in_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        for (wid, ballot_id, election_id, position_idx) in &matches {
+            conn.execute(
+                "UPDATE write_ins SET status=?1, candidate_id=?2, resolved_at=?3 WHERE id=?4",
+                params![status, candidate_id, now, wid])?;
+            if let Some(cid) = candidate_id {
+                conn.execute(
+                    "INSERT INTO votes (id, ballot_id, election_id, position_idx, candidate_id, write_in_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![random_id(conn, "votes")?, ballot_id, election_id, position_idx, cid, wid])?;
+            }
+        }
+        Ok(matches.len())
+    }
+
     // ------------------ Voter Management ------------------
-    pub fn register_voter(&self, fullname: &str, dob: &str, pin: &str) -> rusqlite::Result<i64> {
-        let pinhash = hash_password(pin);
+    /// Register a voter in a district (and optionally one of its polling
+    /// stations) and issue their voter number. The district may only be left
+    /// out while none exist; a district official's voters go in their own
+    /// district. Refuses a second registration for the same name and date
+    /// of birth.
+    pub fn register_voter(
+        &self,
+        fullname: &str,
+        dob: &str,
+        pin: &str,
+        district: Option<&str>,
+        station: Option<&str>,
+    ) -> AppResult<Voter> {
+        self.require(Permission::ManageVoters)?;
+        let fullname = fullname.trim();
+        if fullname.is_empty() || dob.trim().is_empty() {
+            return Err(AppError::Invalid("full name and date of birth are required".to_string()));
+        }
+        let scope = self.voter_scope()?;
+        let placement = match (district, scope) {
+            (Some(d), _) => {
+                let placement = district::resolve(self.conn, d, station)?;
+                self.require_in_scope(scope, Some(placement.0))?;
+                Some(placement)
+            }
+            (None, Some((district_id, station_id))) => match station {
+                Some(s) => Some((district_id, Some(district::find_station(self.conn, district_id, s)?))),
+                None => Some((district_id, station_id)),
+            },
+            (None, None) if district::has_districts(self.conn)? => {
+                return Err(AppError::Invalid("a district is required; see `admin district list`".to_string()));
+            }
+            (None, None) => None,
+        };
+        let existing: Option<String> = self.conn
+            .query_row(
+                "SELECT voter_number FROM voters WHERE LOWER(TRIM(fullname))=LOWER(?1) AND TRIM(dob)=TRIM(?2)",
+                params![fullname, dob], |row| row.get(0))
+            .optional()?;
+        if let Some(number) = existing {
+            return Err(AppError::Invalid(format!(
+                "{fullname} (born {dob}) is already registered as voter {number}")));
+        }
+
+        let voter_number = new_voter_number(self.conn)?;
+        let pinhash = auth::hash_new_pin(self.conn, None, pin)?;
         self.conn.execute(
-            "INSERT INTO voters(fullname, dob, pinhash) VALUES(?,?,?)",
-            params![fullname, dob, pinhash])?;
-        Ok(self.conn.last_insert_rowid())
+            "INSERT INTO voters(voter_number, fullname, dob, pinhash) VALUES(?,?,?,?)",
+            params![voter_number, fullname, dob, pinhash])?;
+        let voter_id = self.conn.last_insert_rowid();
+        auth::remember_password(self.conn, &auth::history_owner("voter", voter_id), &pinhash)?;
+        if let Some((district_id, station_id)) = placement {
+            district::assign_voter(self.conn, voter_id, district_id, station_id)?;
+        }
+        self.audit("register_voter", &format!("voter #{voter_id} ({voter_number})"))?;
+        Ok(Voter { id: voter_id, voter_number, fullname: fullname.to_string(), dob: dob.to_string() })
+    }
+
+    /// Replace a voter's PIN with a random temporary one that must be
+    /// changed at their next login. Returns the voter and the temporary PIN.
+    pub fn reset_voter_pin(&self, voter_number: &str) -> AppResult<(Voter, String)> {
+        self.require(Permission::ManageVoters)?;
+        let voter = self.find_voter_in_scope(voter_number)?;
+        let pin = auth::temporary_pin(self.conn)?;
+        let hash = auth::hash_password(self.conn, &pin)?;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("UPDATE voters SET pinhash=?1, pin_must_change=1 WHERE id=?2", params![hash, voter.id])?;
+        auth::remember_password(&tx, &auth::history_owner("voter", voter.id), &hash)?;
+        self.audit("reset_voter_pin", &format!("voter #{} ({})", voter.id, voter.voter_number))?;
+        tx.commit()?;
+        Ok((voter, pin))
+    }
+
+    fn find_voter(&self, voter_number: &str) -> AppResult<Voter> {
+        let voter_number = normalize_code(voter_number);
+        self.conn
+            .query_row(
+                "SELECT id, voter_number, fullname, dob FROM voters WHERE voter_number=?1",
+                params![voter_number],
+                |row| Ok(Voter { id: row.get(0)?, voter_number: row.get(1)?, fullname: row.get(2)?, dob: row.get(3)? }))
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("voter {voter_number}")))
+    }
+
+    /// Where this admin may manage voters: anywhere (None) if any of their
+    /// roles manages voters everywhere, otherwise only in the district (and
+    /// station) of their poll book.
+    fn voter_scope(&self) -> AppResult<Option<(i64, Option<i64>)>> {
+        let roles = auth::roles_of(self.conn, self.session.admin_id)?;
+        if roles.iter().any(|r| *r != Role::DistrictOfficial && r.allows(Permission::ManageVoters)) {
+            return Ok(None);
+        }
+        Ok(Some(self.poll_book_scope()?))
+    }
+
+    /// Refuse a district outside the admin's voter scope.
+    fn require_in_scope(&self, scope: Option<(i64, Option<i64>)>, district_id: Option<i64>) -> AppResult<()> {
+        match scope {
+            Some((own, _)) if district_id != Some(own) => {
+                self.audit("permission_denied", &format!("outside district #{own}"))?;
+                Err(AppError::Invalid(format!(
+                    "'{}' may only manage voters in district '{}'", self.session.username, district::district_name(self.conn, own)?)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Look a voter up for an admin action; district officials only find
+    /// voters on their own district's roll.
+    fn find_voter_in_scope(&self, voter_number: &str) -> AppResult<Voter> {
+        let scope = self.voter_scope()?;
+        let voter = self.find_voter(voter_number)?;
+        self.require_in_scope(scope, district::voter_district(self.conn, voter.id)?)?;
+        Ok(voter)
+    }
+
+    /// Hand a checked-in voter a single-use token for an open election.
+    /// Returns the voter, the code to print and when it expires.
+    pub fn issue_voting_token(&self, voter_number: &str, election_id: i64) -> AppResult<(Voter, String, String)> {
+        self.require(Permission::ManageVoters)?;
+        let voter = self.find_voter_in_scope(voter_number)?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Open], "issue voting tokens")?;
+        if has_voted(self.conn, voter.id, election_id) {
+            return Err(AppError::AlreadyVoted { election_id });
+        }
+        let (code, expires_at) = token::issue(self.conn, voter.id, election_id, self.session.admin_id)?;
+        self.audit("issue_voting_token", &format!("voter #{} for election #{election_id} until {expires_at}", voter.id))?;
+        Ok((voter, code, expires_at))
+    }
+
+    // ------------------ Districts ------------------
+    /// Create a district with any number of polling stations.
+    pub fn add_district(&self, name: &str, stations: &[String]) -> AppResult<i64> {
+        self.require(Permission::ManageElections)?;
+        let tx = self.conn.unchecked_transaction()?;
+        let district_id = district::add_district(&tx, name)?;
+        for station in stations {
+            district::add_station(&tx, district_id, station)?;
+        }
+        self.audit("add_district", &format!("district #{district_id} '{}' with {} station(s)", name.trim(), stations.len()))?;
+        tx.commit()?;
+        Ok(district_id)
+    }
+
+    /// Add a polling station to an existing district.
+    pub fn add_station(&self, district: &str, name: &str) -> AppResult<i64> {
+        self.require(Permission::ManageElections)?;
+        let district_id = district::find_district(self.conn, district)?;
+        let station_id = district::add_station(self.conn, district_id, name)?;
+        self.audit("add_station", &format!("station #{station_id} '{}' in district #{district_id}", name.trim()))?;
+        Ok(station_id)
+    }
+
+    pub fn list_districts(&self) -> AppResult<Vec<District>> {
+        self.require(Permission::ManageVoters)?;
+        Ok(district::list_districts(self.conn)?)
+    }
+
+    /// Move a registered voter to a district and optional polling station.
+    /// A district official can only move voters between their own
+    /// district's stations.
+    pub fn assign_voter(&self, voter_number: &str, district: &str, station: Option<&str>) -> AppResult<Voter> {
+        self.require(Permission::ManageVoters)?;
+        let voter = self.find_voter_in_scope(voter_number)?;
+        let (district_id, station_id) = district::resolve(self.conn, district, station)?;
+        self.require_in_scope(self.voter_scope()?, Some(district_id))?;
+        district::assign_voter(self.conn, voter.id, district_id, station_id)?;
+        let station = station_id.map(|s| format!(" station #{s}")).unwrap_or_default();
+        self.audit("assign_voter", &format!("voter #{} to district #{district_id}{station}", voter.id))?;
+        Ok(voter)
+    }
+
+    /// Contest a position only in the named districts (none: everywhere).
+    pub fn scope_position(&self, election_id: i64, position_idx: i32, districts: &[String]) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Draft], "change where positions are contested")?;
+        if !list_positions(self.conn, election_id)?.iter().any(|p| p.idx == position_idx) {
+            return Err(AppError::NotFound(format!("position {position_idx} in election #{election_id}")));
+        }
+        let ids = districts.iter().map(|d| district::find_district(self.conn, d)).collect::<AppResult<Vec<i64>>>()?;
+        district::scope_position(self.conn, election_id, position_idx, &ids)?;
+        let scope = if districts.is_empty() { "all districts".to_string() } else { districts.join(", ") };
+        self.audit("scope_position", &format!("election #{election_id} position {position_idx}: {scope}"))?;
+        Ok(())
+    }
+
+    /// Tie an admin (usually a district official) to the district, and
+    /// optionally the polling station, whose poll book they keep.
+    pub fn assign_official(&self, username: &str, district: &str, station: Option<&str>) -> AppResult<()> {
+        self.require(Permission::ManageAdmins)?;
+        let admin_id = auth::admin_id(self.conn, username)?;
+        let (district_id, station_id) = district::resolve(self.conn, district, station)?;
+        district::assign_official(self.conn, admin_id, district_id, station_id)?;
+        let station = station_id.map(|s| format!(" station #{s}")).unwrap_or_default();
+        self.audit("assign_official", &format!("admin #{admin_id} '{username}' to district #{district_id}{station}"))?;
+        Ok(())
+    }
+
+    // ------------------ Poll Book ------------------
+    /// The district and station this official keeps the poll book for.
+    pub fn poll_book_scope(&self) -> AppResult<(i64, Option<i64>)> {
+        self.require(Permission::ManageVoters)?;
+        match district::official_placement(self.conn, self.session.admin_id)? {
+            (Some(district_id), station_id) => Ok((district_id, station_id)),
+            (None, _) => Err(AppError::Invalid(format!(
+                "'{}' is not assigned to a district; see `admin district assign-official`", self.session.username))),
+        }
+    }
+
+    /// Voters on the official's district roll whose voter number is `query`
+    /// or whose name contains it, with their check-in and voted status.
+    pub fn find_on_roll(&self, election_id: i64, query: &str) -> AppResult<Vec<PollBookEntry>> {
+        let (district_id, _) = self.poll_book_scope()?;
+        let query = query.trim();
+        if query.is_empty() {
+            return Err(AppError::Invalid("enter a voter number or part of a name".to_string()));
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT v.id, v.voter_number, v.fullname, v.dob, s.name, c.checked_in_at,
+                EXISTS(SELECT 1 FROM participation p WHERE p.election_id=?1 AND p.voter_id=v.id)
+             FROM voters v
+             LEFT JOIN polling_stations s ON s.id = v.station_id
+             LEFT JOIN check_ins c ON c.voter_id = v.id AND c.election_id = ?1
+             WHERE v.district_id=?2 AND (v.voter_number=?3 OR LOWER(v.fullname) LIKE '%' || LOWER(?4) || '%')
+             ORDER BY v.fullname LIMIT 50")?;
+        let rows = stmt.query_map(params![election_id, district_id, normalize_code(query), query], |row| {
+            Ok(PollBookEntry {
+                voter: Voter { id: row.get(0)?, voter_number: row.get(1)?, fullname: row.get(2)?, dob: row.get(3)? },
+                station: row.get(4)?,
+                checked_in_at: row.get(5)?,
+                voted: row.get(6)?,
+            })
+        })?;
+        let entries: Vec<PollBookEntry> = rows.filter_map(|r| r.ok()).collect();
+        self.audit("poll_book_lookup", &format!("election #{election_id}: '{query}' ({} match(es))", entries.len()))?;
+        Ok(entries)
+    }
+
+    /// Mark a voter on the official's roll as checked in for an open election.
+    pub fn check_in(&self, election_id: i64, voter_number: &str) -> AppResult<Voter> {
+        let (district_id, station_id) = self.poll_book_scope()?;
+        election::require_status(self.conn, election_id, &[ElectionStatus::Open], "check voters in")?;
+        let voter = self.find_voter(voter_number)?;
+        let (voter_district, voter_station) = district::voter_placement(self.conn, voter.id)?;
+        if voter_district != Some(district_id) {
+            return Err(AppError::Invalid(format!("voter {} is not on this district's roll", voter.voter_number)));
+        }
+        if has_voted(self.conn, voter.id, election_id) {
+            return Err(AppError::AlreadyVoted { election_id });
+        }
+        let added = self.conn.execute(
+            "INSERT OR IGNORE INTO check_ins (election_id, voter_id, district_id, station_id, checked_in_by, checked_in_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![election_id, voter.id, district_id, station_id.or(voter_station), self.session.admin_id, Utc::now().to_rfc3339()])?;
+        if added == 0 {
+            return Err(AppError::Invalid(format!("voter {} is already checked in", voter.voter_number)));
+        }
+        self.audit("check_in", &format!("voter #{} for election #{election_id}", voter.id))?;
+        Ok(voter)
+    }
+
+    /// Voters on the official's district roll who have voted in the
+    /// election. Comes from participation only, never from ballots.
+    pub fn voted_on_roll(&self, election_id: i64) -> AppResult<Vec<Voter>> {
+        let (district_id, _) = self.poll_book_scope()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT v.id, v.voter_number, v.fullname, v.dob FROM participation p JOIN voters v ON v.id = p.voter_id
+             WHERE p.election_id=?1 AND v.district_id=?2 ORDER BY v.fullname")?;
+        let rows = stmt.query_map(params![election_id, district_id], |row| {
+            Ok(Voter { id: row.get(0)?, voter_number: row.get(1)?, fullname: row.get(2)?, dob: row.get(3)? })
+        })?;
+        let voters: Vec<Voter> = rows.filter_map(|r| r.ok()).collect();
+        self.audit("poll_book_voted", &format!("election #{election_id}: {} voter(s)", voters.len()))?;
+        Ok(voters)
+    }
+
+    /// Check-ins and ballots cast so far at the official's station, or
+    /// across their district when they are not at a particular station.
+    pub fn station_counts(&self, election_id: i64) -> AppResult<(u64, u64)> {
+        let (district_id, station_id) = self.poll_book_scope()?;
+        let (column, area) = match station_id {
+            Some(s) => ("station_id", s),
+            None => ("district_id", district_id),
+        };
+        let count = |table: &str| -> rusqlite::Result<u64> {
+            self.conn.query_row(
+                &format!("SELECT COUNT(*) FROM {table} WHERE election_id=?1 AND {column}=?2"),
+                params![election_id, area],
+                |row| row.get::<_, i64>(0),
+            ).map(|n| n as u64)
+        };
+        Ok((count("check_ins")?, count("ballots")?))
+    }
+
+    // ------------------ Offline Stations ------------------
+    /// Run this database as an offline polling station and create its
+    /// signing key. Returns the public key to trust on the central instance.
+    pub fn init_station(&self, district: &str, station: &str, passphrase: &str) -> AppResult<String> {
+        self.require(Permission::ManageElections)?;
+        let district_id = district::find_district(self.conn, district)?;
+        let station_id = district::find_station(self.conn, district_id, station)?;
+        let public_key = station::init_identity(self.conn, station_id, passphrase)?;
+        self.audit("init_station", &format!("station #{station_id} key {public_key}"))?;
+        Ok(public_key)
+    }
+
+    /// District, station and public key this database signs results as.
+    pub fn station_identity(&self) -> AppResult<Option<(String, String, String)>> {
+        self.require(Permission::ViewResults)?;
+        let Some((station_id, public_key)) = station::identity(self.conn)? else {
+            return Ok(None);
+        };
+        let (district, station) = station::station_names(self.conn, station_id)?;
+        Ok(Some((district, station, public_key)))
+    }
+
+    /// Sign the results of this station's closed box for central.
+    pub fn export_station_results(&self, election_id: i64, passphrase: &str) -> AppResult<(StationReport, String)> {
+        self.require(Permission::RunElections)?;
+        let (report, package) = station::export(self.conn, election_id, passphrase)?;
+        self.audit("export_station_results", &format!(
+            "election #{election_id}: package {} with {} ballot(s), box {}",
+            report.package_id, report.ballot_count, report.ballot_box_hash))?;
+        Ok((report, package))
+    }
+
+    /// Trust a station's public key for the result packages it sends.
+    pub fn trust_station(&self, district: &str, station: &str, public_key: &str) -> AppResult<()> {
+        self.require(Permission::ManageElections)?;
+        let district_id = district::find_district(self.conn, district)?;
+        let station_id = district::find_station(self.conn, district_id, station)?;
+        station::trust(self.conn, station_id, public_key, self.session.admin_id)?;
+        self.audit("trust_station", &format!("station #{station_id} key {}", public_key.trim()))?;
+        Ok(())
+    }
+
+    /// Verify and record a station's result package. Rejections are
+    /// audited with the reason.
+    pub fn import_station_results(&self, package: &str) -> AppResult<StationReport> {
+        self.require(Permission::RunElections)?;
+        match station::import(self.conn, package, self.session.admin_id) {
+            Ok(report) => {
+                self.audit("import_station_results", &format!(
+                    "election #{} from '{}' / '{}': package {} with {} ballot(s), box {}",
+                    report.election_id, report.district, report.station, report.package_id,
+                    report.ballot_count, report.ballot_box_hash))?;
+                Ok(report)
+            }
+            Err(e) => {
+                self.audit("reject_station_results", &e.to_string())?;
+                Err(e)
+            }
+        }
     }
 
     #[allow(dead_code)]
-    pub fn remove_voter(&self, voter_id: i64) -> rusqlite::Result<()> {
+    pub fn remove_voter(&self, voter_id: i64) -> AppResult<()> {
+        self.require(Permission::ManageVoters)?;
         self.conn.execute("DELETE FROM voters WHERE id=?1", params![voter_id])?;
+        self.audit("remove_voter", &format!("voter #{voter_id}"))?;
+        Ok(())
+    }
+
+    // ------------------ Sessions ------------------
+    /// Force an admin (possibly oneself) to log in again everywhere.
+    pub fn revoke_sessions(&self, username: &str) -> AppResult<usize> {
+        if username != self.session.username {
+            self.require(Permission::ManageAdmins)?;
+        }
+        let n = auth::revoke_sessions(self.conn, username)?;
+        self.audit("revoke_sessions", &format!("{n} session(s) of '{username}'"))?;
+        Ok(n)
+    }
+
+    // ------------------ Admin Accounts ------------------
+    /// Create another admin account with the given roles, recording who created it.
+    pub fn add_admin(&self, username: &str, password: &str, roles: &[Role]) -> AppResult<i64> {
+        self.require(Permission::ManageAdmins)?;
+        if username.trim().is_empty() {
+            return Err(AppError::Invalid("username must not be empty".to_string()));
+        }
+        if auth::admin_id(self.conn, username).is_ok() {
+            return Err(AppError::Invalid(format!("admin '{username}' already exists")));
+        }
+        let hash = auth::hash_new_password(self.conn, None, username, password)?;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO admins (username, password_hash, created_at, created_by) VALUES (?1, ?2, ?3, ?4)",
+            params![username, hash, Utc::now().to_rfc3339(), self.session.admin_id])?;
+        let admin_id = tx.last_insert_rowid();
+        auth::remember_password(&tx, &auth::history_owner("admin", admin_id), &hash)?;
+        for role in roles {
+            auth::grant_role(&tx, admin_id, *role, Some(self.session.admin_id))?;
+        }
+        let roles: Vec<&str> = roles.iter().map(|r| r.as_str()).collect();
+        self.audit("create_admin", &format!("admin #{admin_id} '{username}' with roles [{}]", roles.join(", ")))?;
+        tx.commit()?;
+        Ok(admin_id)
+    }
+
+    pub fn list_admins(&self) -> AppResult<Vec<AdminAccount>> {
+        self.require(Permission::ManageAdmins)?;
+        let mut stmt = self.conn.prepare(
+            "SELECT a.id, a.username, a.created_at, c.username, a.disabled_at IS NOT NULL
+             FROM admins a LEFT JOIN admins c ON c.id = a.created_by ORDER BY a.id")?;
+        let rows: Vec<AdminAccount> = stmt
+            .query_map([], |row| {
+                Ok(AdminAccount {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    created_at: row.get(2)?,
+                    created_by: row.get(3)?,
+                    disabled: row.get(4)?,
+                    roles: Vec::new(),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        let mut accounts = Vec::new();
+        for mut account in rows {
+            account.roles = auth::roles_of(self.conn, account.id)?;
+            accounts.push(account);
+        }
+        Ok(accounts)
+    }
+
+    /// Stop an admin from logging in and end their open sessions. Neither
+    /// oneself nor the last active super-admin can be disabled.
+    pub fn disable_admin(&self, username: &str) -> AppResult<()> {
+        self.require(Permission::ManageAdmins)?;
+        let admin_id = auth::admin_id(self.conn, username)?;
+        if admin_id == self.session.admin_id {
+            return Err(AppError::Invalid("you cannot disable your own account".to_string()));
+        }
+        let other_supers: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM admin_roles r JOIN admins a ON a.id = r.admin_id
+             WHERE r.role=?1 AND a.id<>?2 AND a.disabled_at IS NULL",
+            params![Role::SuperAdmin, admin_id], |row| row.get(0))?;
+        if other_supers == 0 {
+            return Err(AppError::Invalid(format!("'{username}' is the last active super-admin")));
+        }
+        let changed = self.conn.execute(
+            "UPDATE admins SET disabled_at=?1 WHERE id=?2 AND disabled_at IS NULL",
+            params![Utc::now().to_rfc3339(), admin_id])?;
+        if changed == 0 {
+            return Err(AppError::Invalid(format!("admin '{username}' is already disabled")));
+        }
+        auth::revoke_sessions(self.conn, username)?;
+        self.audit("disable_admin", &format!("admin #{admin_id} '{username}'"))?;
+        Ok(())
+    }
+
+    pub fn enable_admin(&self, username: &str) -> AppResult<()> {
+        self.require(Permission::ManageAdmins)?;
+        let admin_id = auth::admin_id(self.conn, username)?;
+        let changed = self.conn.execute(
+            "UPDATE admins SET disabled_at=NULL WHERE id=?1 AND disabled_at IS NOT NULL", params![admin_id])?;
+        if changed == 0 {
+            return Err(AppError::Invalid(format!("admin '{username}' is not disabled")));
+        }
+        self.audit("enable_admin", &format!("admin #{admin_id} '{username}'"))?;
+        Ok(())
+    }
+
+    /// Set a new password for an admin (anyone may change their own) and
+    /// end their open sessions. The two-factor secret is sealed under the old
+    /// password, so enrollment is removed too; returns whether it was.
+    pub fn reset_admin_password(&self, username: &str, new_password: &str) -> AppResult<bool> {
+        if username != self.session.username {
+            self.require(Permission::ManageAdmins)?;
+        }
+        let admin_id = auth::admin_id(self.conn, username)?;
+        let hash = auth::hash_new_password(self.conn, Some(admin_id), username, new_password)?;
+        let had_totp = auth::has_totp(self.conn, admin_id)?;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("UPDATE admins SET password_hash=?1 WHERE id=?2", params![hash, admin_id])?;
+        auth::remember_password(&tx, &auth::history_owner("admin", admin_id), &hash)?;
+        auth::disable_totp(&tx, admin_id)?;
+        auth::revoke_sessions(&tx, username)?;
+        let detail = if had_totp { " (two-factor removed)" } else { "" };
+        self.audit("reset_admin_password", &format!("admin #{admin_id} '{username}'{detail}"))?;
+        tx.commit()?;
+        Ok(had_totp)
+    }
+
+    // ------------------ Two-Factor ------------------
+    /// Start two-factor enrollment for the logged-in admin. Returns the
+    /// provisioning URI and the recovery codes to write down.
+    pub fn enroll_totp(&self, password: &str) -> AppResult<(String, Vec<String>)> {
+        let enrolled = auth::enroll_totp(self.conn, self.session.admin_id, &self.session.username, password)?;
+        self.audit("totp_enroll", "secret and recovery codes issued")?;
+        Ok(enrolled)
+    }
+
+    /// Turn two-factor on once the app produces a matching code.
+    pub fn confirm_totp(&self, password: &str, code: &str) -> AppResult<()> {
+        auth::confirm_totp(self.conn, self.session.admin_id, password, code)?;
+        self.audit("totp_enable", "two-factor required at login")?;
+        Ok(())
+    }
+
+    /// Remove two-factor from the logged-in admin, or from another admin
+    /// (e.g. one who lost their device) with ManageAdmins.
+    pub fn disable_totp(&self, username: Option<&str>) -> AppResult<()> {
+        let username = username.unwrap_or(&self.session.username);
+        if username != self.session.username {
+            self.require(Permission::ManageAdmins)?;
+        }
+        let admin_id = auth::admin_id(self.conn, username)?;
+        if !auth::has_totp(self.conn, admin_id)? {
+            return Err(AppError::Invalid(format!("admin '{username}' has no two-factor enrollment")));
+        }
+        auth::disable_totp(self.conn, admin_id)?;
+        self.audit("totp_disable", &format!("admin #{admin_id} '{username}'"))?;
+        Ok(())
+    }
+
+    // ------------------ Lockouts & Settings ------------------
+    pub fn lockouts(&self) -> AppResult<Vec<Lockout>> {
+        self.require(Permission::ManageAdmins)?;
+        Ok(lockout::list(self.conn)?)
+    }
+
+    /// Clear failed-login counters for one key (e.g. "voter:7kq2-m9xd") or all.
+    pub fn clear_lockout(&self, key: Option<&str>) -> AppResult<usize> {
+        self.require(Permission::ManageAdmins)?;
+        let n = lockout::clear(self.conn, key)?;
+        self.audit("clear_lockout", key.unwrap_or("all"))?;
+        Ok(n)
+    }
+
+    /// Every known setting with its current value.
+    pub fn settings(&self) -> AppResult<Vec<(&'static config::Setting, i64)>> {
+        self.require(Permission::ManageAdmins)?;
+        config::SETTINGS
+            .iter()
+            .map(|s| Ok((s, config::get(self.conn, s.key)?)))
+            .collect()
+    }
+
+    pub fn set_setting(&self, key: &str, value: i64) -> AppResult<()> {
+        self.require(Permission::ManageAdmins)?;
+        let old = config::get(self.conn, key)?;
+        let tx = self.conn.unchecked_transaction()?;
+        config::set(&tx, key, value)?;
+        // The Argon2 costs are only valid in combination; refuse a change
+        // that would make every new hash (and so every login) fail.
+        auth::hash_params(&tx)?;
+        self.audit("set_setting", &format!("{key}: {old} -> {value}"))?;
+        tx.commit()?;
         Ok(())
     }
 
+    // ------------------ Roles ------------------
+    pub fn grant_role(&self, username: &str, role: Role) -> AppResult<bool> {
+        self.require(Permission::ManageAdmins)?;
+        let admin_id = auth::admin_id(self.conn, username)?;
+        let granted = auth::grant_role(self.conn, admin_id, role, Some(self.session.admin_id))?;
+        if granted {
+            self.audit("grant_role", &format!("{role} to '{username}'"))?;
+        }
+        Ok(granted)
+    }
+
+    /// Take a role away. The last super-admin cannot lose the role, or no
+    /// one could ever grant roles again.
+    pub fn revoke_role(&self, username: &str, role: Role) -> AppResult<bool> {
+        self.require(Permission::ManageAdmins)?;
+        let admin_id = auth::admin_id(self.conn, username)?;
+        if role == Role::SuperAdmin {
+            let holders: i64 = self.conn.query_row(
+                "SELECT COUNT(*) FROM admin_roles r JOIN admins a ON a.id = r.admin_id
+                 WHERE r.role=?1 AND a.id<>?2 AND a.disabled_at IS NULL",
+                params![Role::SuperAdmin, admin_id], |row| row.get(0))?;
+            if holders == 0 {
+                return Err(AppError::Invalid(format!("'{username}' is the last active super-admin")));
+            }
+        }
+        let revoked = self.conn.execute(
+            "DELETE FROM admin_roles WHERE admin_id=?1 AND role=?2", params![admin_id, role])? > 0;
+        if revoked {
+            self.audit("revoke_role", &format!("{role} from '{username}'"))?;
+        }
+        Ok(revoked)
+    }
+
+    /// Every admin and the roles they hold.
+    pub fn list_roles(&self) -> AppResult<Vec<(String, Vec<Role>)>> {
+        self.require(Permission::ManageAdmins)?;
+        let mut stmt = self.conn.prepare("SELECT id, username FROM admins ORDER BY username")?;
+        let admins: Vec<(i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        let mut out = Vec::new();
+        for (id, username) in admins {
+            out.push((username, auth::roles_of(self.conn, id)?));
+        }
+        Ok(out)
+    }
+
     // ------------------ Audit & Reporting ------------------
-    pub fn list_elections(&self) -> rusqlite::Result<Vec<Election>> {
-        let mut stmt = self.conn.prepare("SELECT id, name, status, created_at FROM elections")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Election {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                status: row.get(2)?,
-                created_at: row.get(3)?,
-            })
-        })?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+
+    /// The most recent audit log entries, newest last.
+    pub fn audit_log(&self, limit: u32) -> AppResult<Vec<AuditEntry>> {
+        self.require(Permission::ViewAudit)?;
+        self.audit("view_audit_log", &format!("last {limit} entries"))?;
+        Ok(audit::recent(self.conn, limit)?)
+    }
+
+    /// Walk the audit log's hash chain; see `audit::verify`.
+    pub fn verify_audit_log(&self) -> AppResult<(u64, Vec<String>)> {
+        self.require(Permission::ViewAudit)?;
+        let checked = audit::verify(self.conn)?;
+        self.audit("verify_audit_log", &format!("{} problem(s) found", checked.1.len()))?;
+        Ok(checked)
+    }
+
+    pub fn view_results(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::ViewResults)?;
+        self.audit("view_results", &format!("election #{election_id}"))?;
+        let candidates = list_candidates(self.conn, election_id)?;
+        let names: HashMap<i64, String> = candidates.iter()
+            .map(|(cid, name, party, _)| (*cid, format!("{name} ({party})")))
+            .collect();
+
+        println!("Election results for #{election_id}:");
+        for position in list_positions(self.conn, election_id)? {
+            let pidx = position.idx;
+            let rules = position.rules;
+            if rules.seats > 1 {
+                println!("  Position {pidx}: {} [{}, {} seats]", position.title, rules.method, rules.seats);
+            } else {
+                println!("  Position {pidx}: {} [{}]", position.title, rules.method);
+            }
+            let eligible: Vec<i64> = candidates.iter()
+                .filter(|(_, _, _, p)| *p == pidx)
+                .map(|(cid, _, _, _)| *cid)
+                .collect();
+
+            match rules.method {
+                BallotMethod::Plurality => {
+                    self.print_plurality(election_id, pidx, &names)?;
+                    if rules.allow_write_in {
+                        self.print_write_in_summary(election_id, pidx)?;
+                    }
+                }
+                BallotMethod::RankedChoice => {
+                    println!("    First preferences:");
+                    self.print_plurality(election_id, pidx, &names)?;
+                    let ballots = ranked_ballots(self.conn, election_id, pidx)?;
+                    println!("    Instant-runoff count:");
+                    tally::print_irv(&tally::irv(&eligible, &ballots), &names);
+                }
+                BallotMethod::Stv => {
+                    println!("    First preferences:");
+                    self.print_plurality(election_id, pidx, &names)?;
+                    let ballots = ranked_ballots(self.conn, election_id, pidx)?;
+                    println!("    Single transferable vote count:");
+                    tally::print_stv(&tally::stv(&eligible, &ballots, rules.seats), &names);
+                }
+                BallotMethod::Approval => {
+                    let ballots = ranked_ballots(self.conn, election_id, pidx)?;
+                    tally::print_totals(&tally::approval(&eligible, &ballots), &names, "approvals", false);
+                }
+                BallotMethod::Score => {
+                    let ballots = scored_ballots(self.conn, election_id, pidx)?;
+                    println!("    Scores out of {}:", rules.max_score);
+                    tally::print_totals(&tally::score(&eligible, &ballots), &names, "points", true);
+                }
+            }
+        }
+
+        let registered = registered_voter_count(self.conn)?;
+        for question in list_questions(self.conn, election_id)? {
+            println!("  Question {}: {} [{}]", question.idx, question.text, question.threshold);
+            let counts = question_tallies(self.conn, question.id)?;
+            tally::print_question(&tally::question(&question, &counts, registered), &question);
+        }
+        Ok(())
+    }
+
+    /// Write-in totals for a position. Adjudicated write-ins are already
+    /// included in the candidate totals above.
+    fn print_write_in_summary(&self, election_id: i64, pidx: i32) -> rusqlite::Result<()> {
+        let write_ins: Vec<WriteIn> = list_write_ins(self.conn, election_id, false)?
+            .into_iter()
+            .filter(|w| w.position_idx == pidx)
+            .collect();
+        let count = |st: WriteInStatus| write_ins.iter().filter(|w| w.status == st).count();
+        let adjudicated = count(WriteInStatus::Merged) + count(WriteInStatus::Created);
+        println!(
+            "    Write-ins: {} adjudicated (included above), {} rejected, {} unresolved",
+            adjudicated, count(WriteInStatus::Rejected), count(WriteInStatus::Pending));
+
+        let mut pending: Vec<(String, usize)> = Vec::new();
+        for w in write_ins.iter().filter(|w| w.status == WriteInStatus::Pending) {
+            match pending.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(w.name.trim())) {
+                Some((_, c)) => *c += 1,
+                None => pending.push((w.name.trim().to_string(), 1)),
+            }
+        }
+        for (name, c) in pending {
+            println!("      unresolved: \"{name}\" -> {c} votes");
+        }
+        Ok(())
+    }
+
+    /// Per-district and per-station breakdown of every position, turnout
+    /// against each district's roll, and a roll-up checking that district
+    /// subtotals add up to the overall totals. Returns false if anything
+    /// failed to reconcile. Stations with fewer ballots than
+    /// `results.min_station_ballots` are never shown on their own.
+    pub fn view_district_results(&self, election_id: i64) -> AppResult<bool> {
+        self.require(Permission::ViewResults)?;
+        self.audit("view_district_results", &format!("election #{election_id}"))?;
+        election::election_status(self.conn, election_id)?;
+        let names: HashMap<i64, String> = list_candidates(self.conn, election_id)?.iter()
+            .map(|(cid, name, party, _)| (*cid, format!("{name} ({party})")))
+            .collect();
+        let districts = district::list_districts(self.conn)?;
+        let turnout = district::turnout(self.conn, election_id)?;
+        let positions = list_positions(self.conn, election_id)?;
+        let mut problems: Vec<String> = Vec::new();
+
+        println!("District results for election #{election_id}:");
+        println!("\n  Turnout:");
+        for t in &turnout {
+            let pct = if t.registered > 0 { t.voted as f64 * 100.0 / t.registered as f64 } else { 0.0 };
+            println!("    {}: {} of {} registered voted ({pct:.1}%), {} ballot(s)", t.name, t.voted, t.registered, t.ballots);
+            if t.voted != t.ballots {
+                problems.push(format!("{}: {} voters marked as voted but {} ballots", t.name, t.voted, t.ballots));
+            }
+        }
+
+        let district_name = |id: Option<i64>| match id {
+            Some(id) => districts.iter().find(|d| d.id == id).map(|d| d.name.clone()).unwrap_or_else(|| format!("district #{id}")),
+            None => "(no district)".to_string(),
+        };
+        let label = |cid: &i64| names.get(cid).cloned().unwrap_or_else(|| format!("#{cid}"));
+        let unit = |p: &Position| if p.rules.method == BallotMethod::Score { "points" } else { "votes" };
+
+        // Small stations are shown merged into one line. If what is left out
+        // of a district's station lines would itself be a small cell (the
+        // district total minus the rest gives it away), none are shown.
+        let min_cell = config::get(self.conn, "results.min_station_ballots")? as u64;
+        let station_ballots = district::station_ballots(self.conn, election_id)?;
+        let station_cells = |d: &District| -> Option<(Vec<i64>, Vec<i64>)> {
+            let count = |s: &PollingStation| station_ballots.get(&s.id).copied().unwrap_or(0);
+            let (large, small): (Vec<&PollingStation>, Vec<&PollingStation>) =
+                d.stations.iter().filter(|s| count(s) > 0).partition(|s| count(s) >= min_cell);
+            let small_ballots: u64 = small.iter().map(|s| count(s)).sum();
+            let merged: Vec<i64> = if small_ballots >= min_cell { small.iter().map(|s| s.id).collect() } else { Vec::new() };
+            let shown: u64 = large.iter().map(|s| count(s)).sum::<u64>() + if merged.is_empty() { 0 } else { small_ballots };
+            let district_ballots = turnout.iter().find(|t| t.district_id == Some(d.id)).map_or(0, |t| t.ballots);
+            let left_out = district_ballots.saturating_sub(shown);
+            if left_out > 0 && left_out < min_cell {
+                return None;
+            }
+            Some((large.iter().map(|s| s.id).collect(), merged))
+        };
+
+        for position in &positions {
+            println!("\n  Position {}: {} [{}]", position.idx, position.title, position.rules.method);
+            let by_district = district::position_totals_by(self.conn, election_id, position, district::Area::District)?;
+            let by_station = district::position_totals_by(self.conn, election_id, position, district::Area::Station)?;
+            let mut areas: Vec<Option<i64>> = by_district.keys().copied().collect();
+            areas.sort_by_key(|id| district_name(*id));
+            for area in areas {
+                println!("    🗺️  {}:", district_name(area));
+                print_marks(&by_district[&area], &label, unit(position), "      ");
+                let Some(d) = districts.iter().find(|d| Some(d.id) == area) else { continue };
+                let Some((large, merged)) = station_cells(d) else {
+                    println!("      🏫 Station breakdown withheld: it would expose fewer than {min_cell} ballots");
+                    continue;
+                };
+                for station in d.stations.iter().filter(|s| large.contains(&s.id)) {
+                    if let Some(marks) = by_station.get(&Some(station.id)) {
+                        println!("      🏫 {}:", station.name);
+                        print_marks(marks, &label, unit(position), "        ");
+                    }
+                }
+                if !merged.is_empty() {
+                    let mut marks: HashMap<i64, u64> = HashMap::new();
+                    for (cid, n) in merged.iter().filter_map(|sid| by_station.get(&Some(*sid))).flatten() {
+                        *marks.entry(*cid).or_default() += n;
+                    }
+                    println!("      🏫 {} station(s) with fewer than {min_cell} ballots each, combined:", merged.len());
+                    print_marks(&marks, &label, unit(position), "        ");
+                }
+            }
+        }
+
+        println!("\n  Roll-up:");
+        let total_ballots: u64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM ballots WHERE election_id=?1", params![election_id], |row| row.get::<_, i64>(0))? as u64;
+        let district_ballots: u64 = turnout.iter().map(|t| t.ballots).sum();
+        println!("    Ballots: {district_ballots} across districts, {total_ballots} overall");
+        if district_ballots != total_ballots {
+            problems.push(format!("district ballots add up to {district_ballots}, not {total_ballots}"));
+        }
+        for position in &positions {
+            let overall = district::position_totals(self.conn, election_id, position)?;
+            let by_district = district::position_totals_by(self.conn, election_id, position, district::Area::District)?;
+            let mut candidates: Vec<i64> = overall.keys().copied().collect();
+            for marks in by_district.values() {
+                candidates.extend(marks.keys().copied());
+            }
+            candidates.sort();
+            candidates.dedup();
+            println!("    Position {}: {}", position.idx, position.title);
+            for cid in candidates {
+                let summed: u64 = by_district.values().filter_map(|m| m.get(&cid)).sum();
+                let total = overall.get(&cid).copied().unwrap_or(0);
+                if summed == total {
+                    println!("      {} -> {total} {}", label(&cid), unit(position));
+                } else {
+                    println!("      {} -> districts {summed}, overall {total} ⚠️  MISMATCH", label(&cid));
+                    problems.push(format!("position {} {}: districts add up to {summed}, overall {total}", position.idx, label(&cid)));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            println!("\n✅ District subtotals reconcile with the overall totals.");
+        } else {
+            println!("\n⚠️  {} discrepancy(ies) found:", problems.len());
+            for p in &problems {
+                println!("   - {p}");
+            }
+        }
+        Ok(problems.is_empty())
+    }
+
+    /// Every station package imported for an election and their combined
+    /// tallies.
+    pub fn view_station_results(&self, election_id: i64) -> AppResult<()> {
+        self.require(Permission::ViewResults)?;
+        self.audit("view_station_results", &format!("election #{election_id}"))?;
+        election::election_status(self.conn, election_id)?;
+        let results = station::imported(self.conn, election_id)?;
+        if results.is_empty() {
+            println!("No station results imported for election #{election_id}.");
+            return Ok(());
+        }
+
+        println!("Station results for election #{election_id}:");
+        for r in &results {
+            let report = &r.report;
+            println!("  🏫 {} / {}: {} ballot(s), exported {}, imported {}",
+                report.district, report.station, report.ballot_count, report.exported_at, r.imported_at);
+            println!("     package {}, box {}", report.package_id, report.ballot_box_hash);
+        }
+
+        println!("\n  Combined:");
+        let ballots: u64 = results.iter().map(|r| r.report.ballot_count).sum();
+        println!("    Ballots: {ballots}");
+        for position in &results[0].report.positions {
+            let unit = if position.method.parse() == Ok(BallotMethod::Score) { "points" } else { "votes" };
+            println!("    Position {}: {} [{}]", position.idx, position.title, position.method);
+            let mut marks: HashMap<i64, u64> = HashMap::new();
+            let mut names: HashMap<i64, String> = HashMap::new();
+            for r in &results {
+                for tally in r.report.positions.iter().filter(|p| p.idx == position.idx) {
+                    for c in &tally.candidates {
+                        *marks.entry(c.candidate_id).or_default() += c.marks;
+                        names.insert(c.candidate_id, format!("{} ({})", c.name, c.party));
+                    }
+                }
+            }
+            let label = |cid: &i64| names.get(cid).cloned().unwrap_or_else(|| format!("#{cid}"));
+            print_marks(&marks, &label, unit, "      ");
+        }
+        for question in &results[0].report.questions {
+            println!("    Question: {}", question.text);
+            for (oid, label, _) in &question.options {
+                let count: u64 = results.iter()
+                    .flat_map(|r| r.report.questions.iter().filter(|q| q.question_id == question.question_id))
+                    .flat_map(|q| q.options.iter().filter(|(id, _, _)| id == oid))
+                    .map(|(_, _, n)| n)
+                    .sum();
+                println!("      {label} -> {count}");
+            }
+        }
+        Ok(())
     }
 
-    pub fn view_results(&self, election_id: i64) -> rusqlite::Result<()> {
+    /// Single-choice totals; for ranked positions these are first preferences.
+    fn print_plurality(&self, election_id: i64, pidx: i32, names: &HashMap<i64, String>) -> rusqlite::Result<()> {
         let mut stmt = self.conn.prepare(
-            "SELECT position_idx, candidate_id, COUNT(*) FROM votes WHERE election_id=?1 GROUP BY position_idx, candidate_id ORDER BY position_idx, COUNT(*) DESC"
+            "SELECT candidate_id, COUNT(*) FROM votes WHERE election_id=?1 AND position_idx=?2 AND (rank IS NULL OR rank=1) GROUP BY candidate_id ORDER BY COUNT(*) DESC, candidate_id"
         )?;
-        let mut rows = stmt.query(params![election_id])?;
-        println!("Election results for #{election_id}:");
+        let mut rows = stmt.query(params![election_id, pidx])?;
         while let Some(r) = rows.next()? {
-            let pidx: i32 = r.get(0)?;
-            let cid: i64 = r.get(1)?;
-            let count: i64 = r.get(2)?;
-            let (name, party): (String, String) = self.conn.query_row(
-                "SELECT name, party FROM candidates WHERE id=?1",
-                params![cid], |row| Ok((row.get(0)?, row.get(1)?)))?;
-            println!("  Position {pidx}: {name} ({party}) -> {count} votes");
+            let cid: i64 = r.get(0)?;
+            let count: i64 = r.get(1)?;
+            let label = names.get(&cid).cloned().unwrap_or_else(|| format!("#{cid}"));
+            println!("    {label} -> {count} votes");
         }
         Ok(())
     }
@@ -134,3 +1274,12 @@ impl<'a> AdminService<'a> {
         // Placeholder for messaging or API integration logic later
     }
 }
+
+/// Candidate lines of a breakdown, most marks first.
+fn print_marks(marks: &HashMap<i64, u64>, label: &dyn Fn(&i64) -> String, unit: &str, indent: &str) {
+    let mut rows: Vec<(&i64, &u64)> = marks.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (cid, n) in rows {
+        println!("{indent}{} -> {n} {unit}", label(cid));
+    }
+}
@@ -0,0 +1,203 @@
+// ============================================================
+// File: audit.rs
+// Purpose: Append-only, tamper-evident record of who did what.
+//
+// Responsibilities:
+// - Record admin actions, voter logins and ballots cast
+// - Chain every entry to the previous one with SHA-256
+// - Verify the chain and report modified or deleted entries
+//
+// Each entry's hash covers its own fields and the previous entry's hash,
+// so editing any row breaks every link after it. IDs come from
+// AUTOINCREMENT and are never reused, so a deleted row leaves a gap, and
+// `audit_head` remembers the last entry so trimming the end is caught too.
+// ============================================================
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::vote::to_hex;
+
+/// `prev_hash` of the very first entry.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: String,
+    pub actor: String,
+    pub action: String,
+    pub detail: String,
+    pub hash: String,
+}
+
+/// Hash of an entry. Fields are length-prefixed so no choice of text can
+/// make two different entries hash the same input.
+fn entry_hash(prev_hash: &str, id: i64, at: &str, actor: &str, action: &str, detail: &str) -> String {
+    let mut hasher = Sha256::new();
+    for field in [prev_hash, &id.to_string(), at, actor, action, detail] {
+        hasher.update(field.len().to_string().as_bytes());
+        hasher.update(b":");
+        hasher.update(field.as_bytes());
+    }
+    to_hex(&hasher.finalize())
+}
+
+/// Append an entry to the log. Call inside the same transaction as the
+/// action being recorded where there is one, so both land or neither does.
+pub fn record(conn: &Connection, actor: &str, action: &str, detail: &str) -> rusqlite::Result<()> {
+    let prev_hash: String = conn
+        .query_row("SELECT last_hash FROM audit_head WHERE id=1", [], |row| row.get(0))
+        .optional()?
+        .unwrap_or_else(|| GENESIS_HASH.to_string());
+    let at = Utc::now().to_rfc3339();
+
+    conn.execute(
+        "INSERT INTO audit_log (at, actor, action, detail, prev_hash, hash) VALUES (?1, ?2, ?3, ?4, ?5, '')",
+        params![at, actor, action, detail, prev_hash],
+    )?;
+    let id = conn.last_insert_rowid();
+    let hash = entry_hash(&prev_hash, id, &at, actor, action, detail);
+    conn.execute("UPDATE audit_log SET hash=?1 WHERE id=?2", params![hash, id])?;
+    conn.execute(
+        "INSERT INTO audit_head (id, last_id, last_hash) VALUES (1, ?1, ?2)
+         ON CONFLICT(id) DO UPDATE SET last_id=excluded.last_id, last_hash=excluded.last_hash",
+        params![id, hash],
+    )?;
+    Ok(())
+}
+
+/// Most recent entries, newest last.
+pub fn recent(conn: &Connection, limit: u32) -> rusqlite::Result<Vec<AuditEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, at, actor, action, detail, hash FROM
+            (SELECT * FROM audit_log ORDER BY id DESC LIMIT ?1) ORDER BY id ASC",
+    )?;
+    let rows = stmt.query_map(params![limit], |row| {
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            at: row.get(1)?,
+            actor: row.get(2)?,
+            action: row.get(3)?,
+            detail: row.get(4)?,
+            hash: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Walk the whole chain. Returns the number of entries checked and a
+/// description of every problem found (empty means the log is intact).
+pub fn verify(conn: &Connection) -> rusqlite::Result<(u64, Vec<String>)> {
+    let mut problems = Vec::new();
+    let mut stmt = conn.prepare("SELECT id, at, actor, action, detail, prev_hash, hash FROM audit_log ORDER BY id ASC")?;
+    let mut rows = stmt.query([])?;
+
+    let mut expected_id = 1;
+    let mut expected_prev = GENESIS_HASH.to_string();
+    let mut checked = 0;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let at: String = row.get(1)?;
+        let actor: String = row.get(2)?;
+        let action: String = row.get(3)?;
+        let detail: String = row.get(4)?;
+        let prev_hash: String = row.get(5)?;
+        let hash: String = row.get(6)?;
+        checked += 1;
+
+        if id != expected_id {
+            problems.push(missing(expected_id, id - 1, ""));
+        }
+        if prev_hash != expected_prev {
+            problems.push(format!("entry #{id} does not link to the entry before it"));
+        }
+        if entry_hash(&prev_hash, id, &at, &actor, &action, &detail) != hash {
+            problems.push(format!("entry #{id} has been modified"));
+        }
+        expected_id = id + 1;
+        expected_prev = hash;
+    }
+
+    let head: Option<(i64, String)> = conn
+        .query_row("SELECT last_id, last_hash FROM audit_head WHERE id=1", [], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()?;
+    match head {
+        Some((last_id, last_hash)) => {
+            if last_id >= expected_id {
+                problems.push(missing(expected_id, last_id, " at the end of the log"));
+            } else if last_hash != expected_prev {
+                problems.push("the last entry does not match the recorded head of the log".to_string());
+            }
+        }
+        None if checked > 0 => problems.push("the head of the log is missing".to_string()),
+        None => {}
+    }
+    Ok((checked, problems))
+}
+
+fn missing(first: i64, last: i64, place: &str) -> String {
+    if first == last {
+        format!("entry #{first}{place} is missing")
+    } else {
+        format!("entries #{first}..#{last}{place} are missing")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    /// A fresh database with three chained entries.
+    fn log_with_three_entries() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        for n in 1..=3 {
+            record(&conn, "chief", "test", &format!("entry {n}")).unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn intact_log_verifies() {
+        let conn = log_with_three_entries();
+        assert_eq!(verify(&conn).unwrap(), (3, Vec::new()));
+    }
+
+    #[test]
+    fn edited_entry_is_reported() {
+        let conn = log_with_three_entries();
+        conn.execute("UPDATE audit_log SET detail='entry two' WHERE id=2", []).unwrap();
+        let (_, problems) = verify(&conn).unwrap();
+        assert_eq!(problems, vec!["entry #2 has been modified".to_string()]);
+    }
+
+    #[test]
+    fn deleted_middle_entry_is_reported() {
+        let conn = log_with_three_entries();
+        conn.execute("DELETE FROM audit_log WHERE id=2", []).unwrap();
+        let (checked, problems) = verify(&conn).unwrap();
+        assert_eq!(checked, 2);
+        assert_eq!(
+            problems,
+            vec!["entry #2 is missing".to_string(), "entry #3 does not link to the entry before it".to_string()]
+        );
+    }
+
+    #[test]
+    fn deleted_tail_is_reported() {
+        let conn = log_with_three_entries();
+        conn.execute("DELETE FROM audit_log WHERE id=3", []).unwrap();
+        let (_, problems) = verify(&conn).unwrap();
+        assert_eq!(problems, vec!["entry #3 at the end of the log is missing".to_string()]);
+    }
+
+    #[test]
+    fn unreadable_entry_is_an_error() {
+        let conn = log_with_three_entries();
+        conn.execute("UPDATE audit_log SET detail=X'FF' WHERE id=2", []).unwrap();
+        assert!(recent(&conn, 10).is_err());
+    }
+}
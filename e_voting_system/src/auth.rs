@@ -13,21 +13,470 @@ use argon2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Algorithm, Argon2, Params, Version,
 };
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
 use rand::rngs::OsRng;
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-pub fn hash_password(pw: &str) -> String {
+use crate::audit;
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::lockout;
+use crate::models::{AdminSession, Role};
+use crate::policy;
+use crate::sealing;
+use crate::totp;
+use crate::vote::{normalize_code, random_code, to_hex};
+
+/// Argon2id cost parameters for new hashes, from the `argon2.*` settings.
+pub fn hash_params(conn: &Connection) -> AppResult<Params> {
+    let memory = config::get(conn, "argon2.memory_kib")? as u32;
+    let iterations = config::get(conn, "argon2.iterations")? as u32;
+    let parallelism = config::get(conn, "argon2.parallelism")? as u32;
+    Params::new(memory, iterations, parallelism, None)
+        .map_err(|e| AppError::Invalid(format!("invalid Argon2 settings: {e}")))
+}
+
+pub fn hash_password(conn: &Connection, pw: &str) -> AppResult<String> {
     let salt = SaltString::generate(&mut OsRng);
-    // The new version of Argon2 doesn't have Params::recommended(),
-    // so we use Params::new(...) manually.
-    let params = Params::new(15000, 2, 1, None).unwrap(); // memory cost, iterations, parallelism
-    let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-    argon.hash_password(pw.as_bytes(), &salt).unwrap().to_string()
+    let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, hash_params(conn)?);
+    argon
+        .hash_password(pw.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| AppError::Invalid(format!("could not hash password: {e}")))
+}
+
+// ------------------ Password Policy ------------------
+
+/// Key for an account's rows in `password_history`, e.g. "admin:3" or "voter:17".
+pub fn history_owner(kind: &str, id: i64) -> String {
+    format!("{kind}:{id}")
+}
+
+/// Hash a new admin password once it meets the policy and is not one of
+/// the account's recent passwords. `admin_id` is None for a new account.
+pub fn hash_new_password(conn: &Connection, admin_id: Option<i64>, username: &str, pw: &str) -> AppResult<String> {
+    policy::check_password(conn, username, pw)?;
+    if let Some(id) = admin_id {
+        check_reuse(conn, &history_owner("admin", id), pw, "password")?;
+    }
+    hash_password(conn, pw)
+}
+
+/// Hash a new voter PIN once it meets the policy and is not one of the
+/// voter's recent PINs. `voter_id` is None for a new registration.
+pub fn hash_new_pin(conn: &Connection, voter_id: Option<i64>, pin: &str) -> AppResult<String> {
+    policy::check_pin(conn, pin)?;
+    if let Some(id) = voter_id {
+        check_reuse(conn, &history_owner("voter", id), pin, "PIN")?;
+    }
+    hash_password(conn, pin)
+}
+
+/// Set a new PIN for a voter after checking the old one, and clear any
+/// pending forced change.
+pub fn change_voter_pin(conn: &Connection, voter_id: i64, old_pin: &str, new_pin: &str) -> AppResult<()> {
+    let stored: String = conn.query_row("SELECT pinhash FROM voters WHERE id=?1", params![voter_id], |row| row.get(0))?;
+    if !verify_password(&stored, old_pin) {
+        return Err(AppError::Unauthenticated("incorrect current PIN".to_string()));
+    }
+    let hash = hash_new_pin(conn, Some(voter_id), new_pin)?;
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("UPDATE voters SET pinhash=?1, pin_must_change=0 WHERE id=?2", params![hash, voter_id])?;
+    remember_password(&tx, &history_owner("voter", voter_id), &hash)?;
+    audit::record(&tx, &format!("voter #{voter_id}"), "change_pin", "")?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// A random PIN of the configured length that meets the policy, for an
+/// admin to hand to a voter who forgot theirs.
+pub fn temporary_pin(conn: &Connection) -> AppResult<String> {
+    let digits = config::get(conn, "pin.digits")?;
+    loop {
+        let pin: String = (0..digits).map(|_| char::from(b'0' + (OsRng.next_u32() % 10) as u8)).collect();
+        if policy::check_pin(conn, &pin).is_ok() {
+            return Ok(pin);
+        }
+    }
+}
+
+fn check_reuse(conn: &Connection, owner: &str, pw: &str, what: &str) -> AppResult<()> {
+    let depth = config::get(conn, "password.history")?;
+    let mut stmt = conn.prepare("SELECT hash FROM password_history WHERE owner=?1 ORDER BY id DESC LIMIT ?2")?;
+    let recent: Vec<String> = stmt
+        .query_map(params![owner, depth], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    if recent.iter().any(|hash| verify_password(hash, pw)) {
+        return Err(AppError::Invalid(format!("{what} must not be one of the last {depth} used")));
+    }
+    Ok(())
+}
+
+/// Remember a newly stored hash for reuse checks. Enough are kept for the
+/// largest history setting, so raising it takes effect straight away.
+pub fn remember_password(conn: &Connection, owner: &str, hash: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO password_history (owner, hash, set_at) VALUES (?1, ?2, ?3)",
+        params![owner, hash, Utc::now().to_rfc3339()],
+    )?;
+    conn.execute(
+        "DELETE FROM password_history WHERE owner=?1 AND id NOT IN
+            (SELECT id FROM password_history WHERE owner=?1 ORDER BY id DESC LIMIT ?2)",
+        params![owner, config::MAX_PASSWORD_HISTORY],
+    )?;
+    Ok(())
 }
 
+/// Check `pw` against a PHC string using the algorithm, version and cost
+/// parameters recorded in it, so older hashes keep working after the
+/// settings change.
 pub fn verify_password(hash: &str, pw: &str) -> bool {
-    if let Ok(parsed) = PasswordHash::new(hash) {
-        Argon2::default().verify_password(pw.as_bytes(), &parsed).is_ok()
+    let Ok(parsed) = PasswordHash::new(hash) else { return false };
+    let (Ok(algorithm), Ok(params)) = (Algorithm::try_from(parsed.algorithm), Params::try_from(&parsed)) else {
+        return false;
+    };
+    let version = parsed.version.and_then(|v| Version::try_from(v).ok()).unwrap_or_default();
+    Argon2::new(algorithm, version, params).verify_password(pw.as_bytes(), &parsed).is_ok()
+}
+
+/// Call after a successful `verify_password`. If `hash` is not Argon2id or
+/// any of its costs is below the configured one, returns a fresh hash of
+/// `pw` for the caller to store. Hashes stronger than the settings are kept.
+pub fn upgraded_hash(conn: &Connection, hash: &str, pw: &str) -> AppResult<Option<String>> {
+    let wanted = hash_params(conn)?;
+    let weaker = match PasswordHash::new(hash) {
+        Ok(parsed) => match (Algorithm::try_from(parsed.algorithm), Params::try_from(&parsed)) {
+            (Ok(Algorithm::Argon2id), Ok(p)) => {
+                p.m_cost() < wanted.m_cost() || p.t_cost() < wanted.t_cost() || p.p_cost() < wanted.p_cost()
+            }
+            _ => true,
+        },
+        Err(_) => true,
+    };
+    if weaker {
+        Ok(Some(hash_password(conn, pw)?))
     } else {
-        false
+        Ok(None)
+    }
+}
+
+// ------------------ Admin Sessions ------------------
+
+/// How long a session lasts unless `admin login --ttl-minutes` says otherwise.
+pub const DEFAULT_SESSION_MINUTES: i64 = 30;
+
+/// Sessions are looked up by a SHA-256 of the token. Tokens are 256 random
+/// bits, so a fast hash is enough; Argon2 is only needed for guessable secrets.
+fn token_hash(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Check an admin's password (and two-factor code, once enrolled) and open
+/// a session lasting `ttl_minutes`. Returns the bearer token (shown to no
+/// one but the session file) and the session.
+pub fn login_admin(
+    conn: &Connection,
+    username: &str,
+    password: &str,
+    otp: Option<&str>,
+    ttl_minutes: i64,
+) -> AppResult<(String, AdminSession)> {
+    if ttl_minutes <= 0 {
+        return Err(AppError::Invalid("session length must be at least one minute".to_string()));
     }
+
+    let account_key = lockout::account_key("admin", username);
+    let keys = [account_key.clone(), lockout::terminal_key()];
+    lockout::check(conn, &keys)?;
+
+    let found: Option<(i64, String, String, Option<String>)> = conn
+        .query_row(
+            "SELECT id, username, password_hash, disabled_at FROM admins WHERE username=?1",
+            params![username],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+
+    let (admin_id, username, stored_hash) = match found {
+        Some((_, stored_user, stored_hash, Some(_))) if verify_password(&stored_hash, password) => {
+            audit::record(conn, &stored_user, "admin_login_failed", "account disabled")?;
+            return Err(AppError::Unauthenticated(format!("admin account '{stored_user}' is disabled")));
+        }
+        Some((id, stored_user, stored_hash, None)) if verify_password(&stored_hash, password) => {
+            (id, stored_user, stored_hash)
+        }
+        Some((_, stored_user, _, _)) => {
+            audit::record(conn, &stored_user, "admin_login_failed", "incorrect password")?;
+            lockout::record_failure(conn, &keys)?;
+            return Err(AppError::Unauthenticated("incorrect username or password".to_string()));
+        }
+        None => {
+            // Unknown names are throttled too, so lockouts don't reveal which accounts exist.
+            audit::record(conn, username, "admin_login_failed", "no such admin")?;
+            lockout::record_failure(conn, &keys)?;
+            return Err(AppError::Unauthenticated("incorrect username or password".to_string()));
+        }
+    };
+    if totp_enabled(conn, admin_id)? {
+        let Some(otp) = otp else {
+            return Err(AppError::TwoFactorRequired);
+        };
+        if !check_second_factor(conn, admin_id, &username, password, otp)? {
+            audit::record(conn, &username, "admin_login_failed", "incorrect two-factor code")?;
+            lockout::record_failure(conn, &keys)?;
+            return Err(AppError::Unauthenticated("incorrect two-factor code".to_string()));
+        }
+    }
+    lockout::record_success(conn, &account_key)?;
+    if let Some(new_hash) = upgraded_hash(conn, &stored_hash, password)? {
+        conn.execute("UPDATE admins SET password_hash=?1 WHERE id=?2", params![new_hash, admin_id])?;
+        audit::record(conn, &username, "rehash_password", "stronger Argon2 parameters")?;
+    }
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    let now = Utc::now();
+    let expires_at = (now + Duration::minutes(ttl_minutes)).to_rfc3339();
+    conn.execute(
+        "INSERT INTO admin_sessions (admin_id, token_hash, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+        params![admin_id, token_hash(&token), now.to_rfc3339(), expires_at],
+    )?;
+    let id = conn.last_insert_rowid();
+    audit::record(conn, &username, "admin_login", &format!("session #{id} until {expires_at}"))?;
+    Ok((token, AdminSession { id, admin_id, username, expires_at }))
+}
+
+/// Resolve a token to its session, refusing unknown, revoked or expired ones.
+pub fn validate_session(conn: &Connection, token: &str) -> AppResult<AdminSession> {
+    let found: Option<(AdminSession, Option<String>)> = conn
+        .query_row(
+            "SELECT s.id, s.admin_id, a.username, s.expires_at, COALESCE(s.revoked_at, a.disabled_at)
+             FROM admin_sessions s JOIN admins a ON a.id = s.admin_id
+             WHERE s.token_hash=?1",
+            params![token_hash(token)],
+            |row| {
+                Ok((
+                    AdminSession { id: row.get(0)?, admin_id: row.get(1)?, username: row.get(2)?, expires_at: row.get(3)? },
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    let Some((session, revoked_at)) = found else {
+        return Err(AppError::Unauthenticated("not logged in; run `admin login` first".to_string()));
+    };
+    if revoked_at.is_some() {
+        return Err(AppError::Unauthenticated("session has been revoked; log in again".to_string()));
+    }
+    let expired = DateTime::parse_from_rfc3339(&session.expires_at).map_or(true, |t| t <= Utc::now());
+    if expired {
+        return Err(AppError::Unauthenticated("session has expired; log in again".to_string()));
+    }
+    Ok(session)
+}
+
+/// End a session. Unknown or already-ended tokens are not an error.
+pub fn logout(conn: &Connection, token: &str) -> AppResult<()> {
+    let session = validate_session(conn, token).ok();
+    conn.execute(
+        "UPDATE admin_sessions SET revoked_at=?1 WHERE token_hash=?2 AND revoked_at IS NULL",
+        params![Utc::now().to_rfc3339(), token_hash(token)],
+    )?;
+    if let Some(s) = session {
+        audit::record(conn, &s.username, "admin_logout", &format!("session #{}", s.id))?;
+    }
+    Ok(())
+}
+
+/// Revoke every open session of an admin. Returns how many were revoked.
+pub fn revoke_sessions(conn: &Connection, username: &str) -> AppResult<usize> {
+    let admin_id = admin_id(conn, username)?;
+    let n = conn.execute(
+        "UPDATE admin_sessions SET revoked_at=?1 WHERE admin_id=?2 AND revoked_at IS NULL",
+        params![Utc::now().to_rfc3339(), admin_id],
+    )?;
+    Ok(n)
+}
+
+// ------------------ Two-Factor (TOTP) ------------------
+
+/// How many single-use recovery codes are issued at enrollment.
+const RECOVERY_CODES: usize = 10;
+
+fn totp_enabled(conn: &Connection, admin_id: i64) -> rusqlite::Result<bool> {
+    conn.query_row("SELECT totp_enabled FROM admins WHERE id=?1", params![admin_id], |row| row.get(0))
+}
+
+/// Whether the admin has a secret stored, confirmed or not.
+pub fn has_totp(conn: &Connection, admin_id: i64) -> rusqlite::Result<bool> {
+    conn.query_row("SELECT totp_secret IS NOT NULL FROM admins WHERE id=?1", params![admin_id], |row| row.get(0))
+}
+
+fn require_password(conn: &Connection, admin_id: i64, password: &str) -> AppResult<()> {
+    let stored: String =
+        conn.query_row("SELECT password_hash FROM admins WHERE id=?1", params![admin_id], |row| row.get(0))?;
+    if verify_password(&stored, password) {
+        Ok(())
+    } else {
+        Err(AppError::Unauthenticated("incorrect password".to_string()))
+    }
+}
+
+/// Decrypt an admin's TOTP secret with their password.
+fn totp_secret(conn: &Connection, admin_id: i64, password: &str) -> AppResult<Vec<u8>> {
+    let (sealed, salt): (Option<String>, Option<String>) = conn.query_row(
+        "SELECT totp_secret, totp_salt FROM admins WHERE id=?1",
+        params![admin_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    match (sealed, salt) {
+        (Some(sealed), Some(salt)) => sealing::open(&sealed, &salt, password, "two-factor secret")?
+            .ok_or_else(|| AppError::Invalid("stored two-factor secret is corrupt".to_string())),
+        _ => Err(AppError::Invalid("two-factor authentication has not been set up; run `admin totp enroll`".to_string())),
+    }
+}
+
+/// Start enrollment: store a new encrypted secret (not yet required at
+/// login) and fresh recovery codes. Returns the provisioning URI and the
+/// recovery codes, which are shown once and only stored hashed.
+pub fn enroll_totp(conn: &Connection, admin_id: i64, username: &str, password: &str) -> AppResult<(String, Vec<String>)> {
+    require_password(conn, admin_id, password)?;
+    let secret = totp::generate_secret();
+    let (sealed, salt) = sealing::seal(&secret, password)?;
+    let codes: Vec<String> = (0..RECOVERY_CODES).map(|_| random_code(12)).collect();
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE admins SET totp_secret=?1, totp_salt=?2, totp_enabled=0, totp_last_step=NULL WHERE id=?3",
+        params![sealed, salt, admin_id],
+    )?;
+    tx.execute("DELETE FROM admin_recovery_codes WHERE admin_id=?1", params![admin_id])?;
+    for code in &codes {
+        tx.execute(
+            "INSERT INTO admin_recovery_codes (admin_id, code_hash) VALUES (?1, ?2)",
+            params![admin_id, token_hash(code)],
+        )?;
+    }
+    tx.commit()?;
+    Ok((totp::provisioning_uri(username, &secret), codes))
+}
+
+/// Finish enrollment with a code from the app, proving it was set up
+/// correctly. From then on the code is required at login.
+pub fn confirm_totp(conn: &Connection, admin_id: i64, password: &str, code: &str) -> AppResult<()> {
+    require_password(conn, admin_id, password)?;
+    let secret = totp_secret(conn, admin_id, password)?;
+    let step = totp::matching_step(&secret, code, totp::step_at(Utc::now().timestamp()))
+        .ok_or_else(|| AppError::Invalid("that code does not match; check the device clock and try again".to_string()))?;
+    conn.execute(
+        "UPDATE admins SET totp_enabled=1, totp_last_step=?1 WHERE id=?2",
+        params![step, admin_id],
+    )?;
+    Ok(())
+}
+
+/// Remove an admin's secret and recovery codes.
+pub fn disable_totp(conn: &Connection, admin_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE admins SET totp_secret=NULL, totp_salt=NULL, totp_enabled=0, totp_last_step=NULL WHERE id=?1",
+        params![admin_id],
+    )?;
+    conn.execute("DELETE FROM admin_recovery_codes WHERE admin_id=?1", params![admin_id])?;
+    Ok(())
+}
+
+/// Accept either a current TOTP code (each time step only once) or an
+/// unused recovery code, which is then spent.
+fn check_second_factor(conn: &Connection, admin_id: i64, username: &str, password: &str, code: &str) -> AppResult<bool> {
+    let secret = totp_secret(conn, admin_id, password)?;
+    if let Some(step) = totp::matching_step(&secret, code, totp::step_at(Utc::now().timestamp())) {
+        // Conditional so the same code cannot log in twice.
+        let fresh = conn.execute(
+            "UPDATE admins SET totp_last_step=?1 WHERE id=?2 AND (totp_last_step IS NULL OR totp_last_step < ?1)",
+            params![step, admin_id],
+        )?;
+        return Ok(fresh > 0);
+    }
+
+    let spent = conn.execute(
+        "UPDATE admin_recovery_codes SET used_at=?1 WHERE admin_id=?2 AND code_hash=?3 AND used_at IS NULL",
+        params![Utc::now().to_rfc3339(), admin_id, token_hash(&normalize_code(code))],
+    )?;
+    if spent > 0 {
+        let left: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM admin_recovery_codes WHERE admin_id=?1 AND used_at IS NULL",
+            params![admin_id],
+            |row| row.get(0),
+        )?;
+        audit::record(conn, username, "recovery_code_used", &format!("{left} left"))?;
+    }
+    Ok(spent > 0)
+}
+
+// ------------------ Session File ------------------
+
+/// The CLI keeps its token next to the database it belongs to,
+/// e.g. `rusttrust.db.session`.
+pub fn session_file(db: &Path) -> PathBuf {
+    let mut name = db.as_os_str().to_owned();
+    name.push(".session");
+    PathBuf::from(name)
+}
+
+pub fn save_session_token(path: &Path, token: &str) -> std::io::Result<()> {
+    let mut opts = OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut file = opts.open(path)?;
+    file.write_all(token.as_bytes())
+}
+
+pub fn load_session_token(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|t| t.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// The session the CLI is currently logged in with.
+pub fn current_session(conn: &Connection, session_path: &Path) -> AppResult<AdminSession> {
+    match load_session_token(session_path) {
+        Some(token) => validate_session(conn, &token),
+        None => Err(AppError::Unauthenticated("not logged in; run `admin login` first".to_string())),
+    }
+}
+
+// ------------------ Roles ------------------
+
+pub fn admin_id(conn: &Connection, username: &str) -> AppResult<i64> {
+    conn.query_row("SELECT id FROM admins WHERE username=?1", params![username], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("admin '{username}'")))
+}
+
+/// Roles currently held by an admin, read fresh so a revocation applies
+/// to sessions that are already open.
+pub fn roles_of(conn: &Connection, admin_id: i64) -> rusqlite::Result<Vec<Role>> {
+    let mut stmt = conn.prepare("SELECT role FROM admin_roles WHERE admin_id=?1 ORDER BY role")?;
+    let rows = stmt.query_map(params![admin_id], |row| row.get(0))?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Give an admin a role. `granted_by` is None only while bootstrapping.
+pub fn grant_role(conn: &Connection, admin_id: i64, role: Role, granted_by: Option<i64>) -> rusqlite::Result<bool> {
+    let n = conn.execute(
+        "INSERT OR IGNORE INTO admin_roles (admin_id, role, granted_by, granted_at) VALUES (?1, ?2, ?3, ?4)",
+        params![admin_id, role, granted_by, Utc::now().to_rfc3339()],
+    )?;
+    Ok(n > 0)
 }
@@ -0,0 +1,152 @@
+// ============================================================
+// File: config.rs
+// Purpose: Tunable security settings stored in the database.
+//
+// Responsibilities:
+// - Declare every known setting with its default and limits
+// - Read settings, falling back to the default when unset
+// - Validate and store changes made by admins
+// ============================================================
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::error::{AppError, AppResult};
+
+/// A known setting: key, default, smallest and largest accepted value, description.
+pub struct Setting {
+    pub key: &'static str,
+    pub default: i64,
+    pub min: i64,
+    pub max: i64,
+    pub description: &'static str,
+}
+
+/// Most old hashes kept per account; `password.history` cannot exceed it.
+pub const MAX_PASSWORD_HISTORY: i64 = 24;
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "argon2.memory_kib",
+        default: 15000,
+        min: 8,
+        max: 4 * 1024 * 1024,
+        description: "Argon2id memory cost in KiB for new password and PIN hashes",
+    },
+    Setting {
+        key: "argon2.iterations",
+        default: 2,
+        min: 1,
+        max: 100,
+        description: "Argon2id time cost (passes over memory)",
+    },
+    Setting {
+        key: "argon2.parallelism",
+        default: 1,
+        min: 1,
+        max: 64,
+        description: "Argon2id lanes; memory must be at least 8 KiB per lane",
+    },
+    Setting {
+        key: "lockout.account_threshold",
+        default: 5,
+        min: 1,
+        max: 100,
+        description: "Failed logins on one account before it is locked",
+    },
+    Setting {
+        key: "lockout.terminal_threshold",
+        default: 20,
+        min: 1,
+        max: 1000,
+        description: "Failed logins from one terminal before it is locked",
+    },
+    Setting {
+        key: "lockout.minutes",
+        default: 15,
+        min: 1,
+        max: 24 * 60,
+        description: "How long a lockout lasts; failures older than this are forgotten",
+    },
+    Setting {
+        key: "lockout.backoff_seconds",
+        default: 1,
+        min: 0,
+        max: 60,
+        description: "Wait after the first failure; doubles with each further failure",
+    },
+    Setting {
+        key: "password.min_length",
+        default: 12,
+        min: 8,
+        max: 128,
+        description: "Fewest characters in a new admin password",
+    },
+    Setting {
+        key: "password.min_classes",
+        default: 3,
+        min: 1,
+        max: 4,
+        description: "How many of lowercase, uppercase, digits and symbols a new password must mix",
+    },
+    Setting {
+        key: "password.history",
+        default: 5,
+        min: 0,
+        max: MAX_PASSWORD_HISTORY,
+        description: "Recent passwords and PINs an account may not reuse (0 to allow reuse)",
+    },
+    Setting {
+        key: "pin.digits",
+        default: 6,
+        min: 4,
+        max: 12,
+        description: "Exact number of digits in a new voter PIN",
+    },
+    Setting {
+        key: "token.minutes",
+        default: 30,
+        min: 1,
+        max: 24 * 60,
+        description: "How long a polling-station voting token stays valid",
+    },
+    Setting {
+        key: "results.min_station_ballots",
+        default: 5,
+        min: 1,
+        max: 1000,
+        description: "Fewest ballots a polling station needs before its results are shown on their own",
+    },
+];
+
+fn setting(key: &str) -> AppResult<&'static Setting> {
+    SETTINGS
+        .iter()
+        .find(|s| s.key == key)
+        .ok_or_else(|| AppError::NotFound(format!("setting '{key}'")))
+}
+
+/// Current value of a setting, or its default when it has never been set.
+pub fn get(conn: &Connection, key: &str) -> AppResult<i64> {
+    let setting = setting(key)?;
+    let stored: Option<i64> = conn
+        .query_row("SELECT value FROM settings WHERE key=?1", params![key], |row| row.get(0))
+        .optional()?;
+    Ok(stored.unwrap_or(setting.default))
+}
+
+/// Store a new value after checking it is within the setting's limits.
+pub fn set(conn: &Connection, key: &str, value: i64) -> AppResult<()> {
+    let setting = setting(key)?;
+    if value < setting.min || value > setting.max {
+        return Err(AppError::Invalid(format!(
+            "{key} must be between {} and {}",
+            setting.min, setting.max
+        )));
+    }
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value=excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
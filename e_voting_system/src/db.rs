@@ -3,8 +3,702 @@
 // Purpose: Manages database operations and data persistence.
 //
 // Responsibilities:
-// - Initialize and connect to the SQLite database
-// - Create tables (voters, candidates, elections, votes)
-// - Handle insert, update, delete, and query operations
-// - Provide helper functions for reading/writing model data
+// - Create and upgrade the schema through numbered migrations
+// - Record which migrations a database has had in `schema_version`
+// - Apply each pending migration in its own transaction
+// - Report the schema status for `db migrate --status`
+//
+// Migrations run in order, once each, and are never edited after release:
+// a schema change is a new entry at the end of `MIGRATIONS`. Databases
+// from before versioning already hold part of this schema, so migrations
+// 1-12 only create what is missing (CREATE IF NOT EXISTS and
+// `add_column_if_missing`) and are safe to run over them. Migrations use
+// only the helpers in this file, never application code, so they keep
+// doing what they did when released.
 // ============================================================
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use rand::Rng;
+use rusqlite::{params, Connection};
+
+use crate::error::{AppError, AppResult};
+use crate::models::Role;
+
+/// One step of the schema's history.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Connection, &mut Notes) -> rusqlite::Result<()>,
+}
+
+/// What a migration did that whoever ran it should hear about, such as
+/// voter numbers to hand out.
+pub type Notes = Vec<String>;
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "Core election, position, candidate and voter tables", apply: core_schema },
+    Migration { version: 2, description: "Anonymous ballot box with tracking receipts", apply: ballot_box },
+    Migration { version: 3, description: "Admin roles and account management", apply: admin_roles },
+    Migration { version: 4, description: "Hash-chained audit log", apply: audit_log },
+    Migration { version: 5, description: "Admin sessions, settings and login throttling", apply: sessions_and_settings },
+    Migration { version: 6, description: "Voter numbers", apply: voter_numbers },
+    Migration { version: 7, description: "Admin two-factor login", apply: two_factor },
+    Migration { version: 8, description: "Password and PIN history", apply: password_history },
+    Migration { version: 9, description: "Polling-station voting tokens and PIN resets", apply: voting_tokens },
+    Migration { version: 10, description: "Districts, polling stations and district-scoped positions", apply: districts },
+    Migration { version: 11, description: "District poll book", apply: poll_book },
+    Migration { version: 12, description: "Offline polling stations and signed result imports", apply: offline_stations },
+    Migration { version: 13, description: "District recorded with participation", apply: participation_district },
+];
+
+/// Highest version this build knows about.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Versions recorded in the database with when they were applied. Empty for
+/// a new database or one from before versioning.
+pub fn applied(conn: &Connection) -> rusqlite::Result<Vec<(u32, String)>> {
+    if !table_exists(conn, "schema_version") {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT version, applied_at FROM schema_version ORDER BY version")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// True if the database has tables but no migration history yet.
+pub fn is_unversioned(conn: &Connection) -> rusqlite::Result<bool> {
+    Ok(!table_exists(conn, "schema_version") && table_exists(conn, "admins"))
+}
+
+/// Apply every pending migration, oldest first. Each one runs in its own
+/// transaction together with its `schema_version` row, so a failure leaves
+/// the database at the last version that completed. Returns the migrations
+/// applied, each with its notes. Refuses a database written by a newer build.
+pub fn migrate(conn: &Connection) -> AppResult<Vec<(&'static Migration, Notes)>> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );",
+    )?;
+    let done: Vec<u32> = applied(conn)?.into_iter().map(|(v, _)| v).collect();
+    if let Some(newest) = done.iter().max().filter(|v| **v > latest_version()) {
+        return Err(AppError::Invalid(format!(
+            "database schema version {newest} is newer than this program supports ({}); upgrade rusttrust",
+            latest_version()
+        )));
+    }
+
+    let mut ran = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| !done.contains(&m.version)) {
+        // Table rebuilds would trip foreign keys half-way through; they are
+        // checked for the whole migration before it commits instead. The
+        // pragma has no effect inside a transaction, so it is set outside.
+        conn.execute_batch("PRAGMA foreign_keys = OFF;")?;
+        let result = apply(conn, migration);
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        let notes = result
+            .map_err(|e| AppError::Invalid(format!("migration {} ({}) failed: {e}", migration.version, migration.description)))?;
+        ran.push((migration, notes));
+    }
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    Ok(ran)
+}
+
+fn apply(conn: &Connection, migration: &Migration) -> AppResult<Notes> {
+    let tx = conn.unchecked_transaction()?;
+    let mut notes = Notes::new();
+    (migration.apply)(&tx, &mut notes)?;
+    let broken: Vec<String> = tx
+        .prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    if let Some(table) = broken.first() {
+        return Err(AppError::Invalid(format!("{} row(s) in {table} and elsewhere refer to missing rows", broken.len())));
+    }
+    tx.execute(
+        "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+        params![migration.version, migration.description, Utc::now().to_rfc3339()],
+    )?;
+    tx.commit()?;
+    Ok(notes)
+}
+
+// ------------------ Helpers ------------------
+
+/// A random, unused row ID for `table`, as `vote::random_id` hands out when
+/// a ballot is cast.
+fn random_id(conn: &Connection, table: &str) -> rusqlite::Result<i64> {
+    let mut rng = rand::thread_rng();
+    loop {
+        let id: i64 = rng.gen_range(1..=i32::MAX as i64);
+        let taken: bool = conn.query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE id=?1)"),
+            params![id],
+            |row| row.get(0),
+        )?;
+        if !taken {
+            return Ok(id);
+        }
+    }
+}
+
+/// A fresh, unused voter number such as `7KQ2-M9XD`, in the format
+/// `voter::new_voter_number` uses at registration.
+fn new_voter_number(conn: &Connection) -> rusqlite::Result<String> {
+    const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let mut rng = rand::thread_rng();
+    loop {
+        let chars: Vec<char> = (0..8).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect();
+        let number = chars.chunks(4).map(|c| c.iter().collect::<String>()).collect::<Vec<_>>().join("-");
+        let taken: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM voters WHERE voter_number=?1)", params![number], |row| row.get(0))?;
+        if !taken {
+            return Ok(number);
+        }
+    }
+}
+
+fn table_exists(conn: &Connection, table: &str) -> bool {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table' AND name=?1)",
+        params![table],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{table}') WHERE name=?1)"),
+        params![column],
+        |row| row.get(0),
+    )
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    if !column_exists(conn, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl};"))?;
+    }
+    Ok(())
+}
+
+// ------------------ Migrations ------------------
+
+fn core_schema(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS admins (
+            id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS elections (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'Draft',
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS positions (
+            id INTEGER PRIMARY KEY,
+            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
+            idx INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            method TEXT NOT NULL DEFAULT 'Plurality',
+            seats INTEGER NOT NULL DEFAULT 1,
+            min_selections INTEGER NOT NULL DEFAULT 0,
+            max_selections INTEGER NOT NULL DEFAULT 0,
+            max_score INTEGER NOT NULL DEFAULT 5,
+            allow_write_in INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS questions (
+            id INTEGER PRIMARY KEY,
+            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
+            idx INTEGER NOT NULL,
+            text TEXT NOT NULL,
+            threshold TEXT NOT NULL DEFAULT 'SimpleMajority',
+            quorum_percent INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS question_options (
+            id INTEGER PRIMARY KEY,
+            question_id INTEGER NOT NULL REFERENCES questions(id) ON DELETE CASCADE,
+            idx INTEGER NOT NULL,
+            label TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS candidates (
+            id INTEGER PRIMARY KEY,
+            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
+            position_idx INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            party TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS voters (
+            id INTEGER PRIMARY KEY,
+            fullname TEXT NOT NULL,
+            dob TEXT NOT NULL,
+            pinhash TEXT NOT NULL
+        );
+    "#,
+    )?;
+    add_column_if_missing(conn, "positions", "method", "TEXT NOT NULL DEFAULT 'Plurality'")?;
+    add_column_if_missing(conn, "positions", "seats", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(conn, "positions", "min_selections", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "positions", "max_selections", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "positions", "max_score", "INTEGER NOT NULL DEFAULT 5")?;
+    add_column_if_missing(conn, "positions", "allow_write_in", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn ballot_box(conn: &Connection, notes: &mut Notes) -> rusqlite::Result<()> {
+    // Databases from before the secret ballot keep `voter_id` on votes. Move
+    // that table aside before the anonymous one is created, then copy its
+    // contents across.
+    let legacy = column_exists(conn, "votes", "voter_id")?;
+    if legacy {
+        conn.execute_batch("ALTER TABLE votes RENAME TO legacy_votes;")?;
+    }
+
+    conn.execute_batch(
+        r#"
+        -- Who has voted where. Never linked to a ballot.
+        CREATE TABLE IF NOT EXISTS participation (
+            id INTEGER PRIMARY KEY,
+            election_id INTEGER NOT NULL,
+            voter_id INTEGER NOT NULL,
+            UNIQUE (election_id, voter_id)
+        );
+
+        -- The anonymous ballot box. Row IDs are random (see random_id)
+        -- and nothing is timestamped, so storage order reveals nothing.
+        CREATE TABLE IF NOT EXISTS ballots (
+            id INTEGER PRIMARY KEY,
+            election_id INTEGER NOT NULL,
+            tracking_code TEXT,
+            content_hash TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS votes (
+            id INTEGER PRIMARY KEY,
+            ballot_id INTEGER NOT NULL REFERENCES ballots(id),
+            election_id INTEGER NOT NULL,
+            position_idx INTEGER NOT NULL,
+            candidate_id INTEGER NOT NULL,
+            rank INTEGER,
+            score INTEGER,
+            write_in_id INTEGER REFERENCES write_ins(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS answers (
+            id INTEGER PRIMARY KEY,
+            ballot_id INTEGER NOT NULL REFERENCES ballots(id),
+            election_id INTEGER NOT NULL,
+            question_id INTEGER NOT NULL,
+            option_id INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS write_ins (
+            id INTEGER PRIMARY KEY,
+            ballot_id INTEGER NOT NULL REFERENCES ballots(id),
+            election_id INTEGER NOT NULL,
+            position_idx INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'Pending',
+            candidate_id INTEGER REFERENCES candidates(id),
+            resolved_at TEXT
+        );
+    "#,
+    )?;
+    if legacy {
+        separate_ballots_from_voters(conn, notes)?;
+    }
+    conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS idx_ballots_tracking_code ON ballots(tracking_code);")
+}
+
+/// Copy `legacy_votes` into the anonymous tables: who voted goes to
+/// `participation`, each voter's votes in an election become one ballot
+/// with a random ID, and nothing links it back to the voter.
+fn separate_ballots_from_voters(conn: &Connection, notes: &mut Notes) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "INSERT OR IGNORE INTO participation (election_id, voter_id)
+            SELECT DISTINCT election_id, voter_id FROM legacy_votes;",
+    )?;
+
+    // (election_id, voter_id, position_idx, candidate_id)
+    let old_votes: Vec<(i64, i64, i32, i64)> = conn
+        .prepare("SELECT election_id, voter_id, position_idx, candidate_id FROM legacy_votes")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut ballot_ids: HashMap<(i64, i64), i64> = HashMap::new();
+    for (election_id, voter_id, position_idx, candidate_id) in old_votes {
+        let ballot_id = match ballot_ids.get(&(election_id, voter_id)) {
+            Some(id) => *id,
+            None => {
+                let id = random_id(conn, "ballots")?;
+                conn.execute("INSERT INTO ballots (id, election_id) VALUES (?1, ?2)", params![id, election_id])?;
+                ballot_ids.insert((election_id, voter_id), id);
+                id
+            }
+        };
+        conn.execute(
+            "INSERT INTO votes (id, ballot_id, election_id, position_idx, candidate_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![random_id(conn, "votes")?, ballot_id, election_id, position_idx, candidate_id],
+        )?;
+    }
+
+    conn.execute_batch("DROP TABLE legacy_votes;")?;
+    notes.push(format!("{} ballot(s) separated from voter identities", ballot_ids.len()));
+    Ok(())
+}
+
+fn admin_roles(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    let had_roles = table_exists(conn, "admin_roles");
+    conn.execute_batch(
+        r#"
+        -- Roles held by each admin (see models::Role).
+        CREATE TABLE IF NOT EXISTS admin_roles (
+            admin_id INTEGER NOT NULL REFERENCES admins(id) ON DELETE CASCADE,
+            role TEXT NOT NULL,
+            granted_by INTEGER REFERENCES admins(id),
+            granted_at TEXT NOT NULL,
+            PRIMARY KEY (admin_id, role)
+        );
+    "#,
+    )?;
+    add_column_if_missing(conn, "admins", "created_by", "INTEGER REFERENCES admins(id)")?;
+    add_column_if_missing(conn, "admins", "disabled_at", "TEXT")?;
+
+    // Admins from before roles existed had full control; keep it that way.
+    if !had_roles {
+        conn.execute(
+            "INSERT INTO admin_roles (admin_id, role, granted_at) SELECT id, ?1, ?2 FROM admins",
+            params![Role::SuperAdmin, Utc::now().to_rfc3339()],
+        )?;
+    }
+    Ok(())
+}
+
+fn audit_log(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+        -- Hash-chained record of admin actions, logins and ballots cast (see audit.rs).
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            at TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            action TEXT NOT NULL,
+            detail TEXT NOT NULL,
+            prev_hash TEXT NOT NULL,
+            hash TEXT NOT NULL
+        );
+
+        -- Last entry of the chain, so entries removed from the end are noticed.
+        CREATE TABLE IF NOT EXISTS audit_head (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            last_id INTEGER NOT NULL,
+            last_hash TEXT NOT NULL
+        );
+    "#,
+    )
+}
+
+fn sessions_and_settings(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+        -- Admin login sessions. Only a hash of each token is kept.
+        CREATE TABLE IF NOT EXISTS admin_sessions (
+            id INTEGER PRIMARY KEY,
+            admin_id INTEGER NOT NULL REFERENCES admins(id) ON DELETE CASCADE,
+            token_hash TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            revoked_at TEXT
+        );
+
+        -- Admin-tunable settings (see config.rs); unset keys use their defaults.
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value INTEGER NOT NULL
+        );
+
+        -- Failed-login counters per account and terminal (see lockout.rs).
+        CREATE TABLE IF NOT EXISTS login_throttle (
+            key TEXT PRIMARY KEY,
+            failures INTEGER NOT NULL,
+            last_failure_at TEXT NOT NULL,
+            locked_until TEXT
+        );
+    "#,
+    )
+}
+
+fn voter_numbers(conn: &Connection, notes: &mut Notes) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "voters", "voter_number", "TEXT")?;
+    assign_voter_numbers(conn, notes)?;
+    conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS idx_voters_voter_number ON voters(voter_number);")
+}
+
+/// Voters registered before voter numbers existed get one now. They are
+/// listed so the numbers can be handed out.
+fn assign_voter_numbers(conn: &Connection, notes: &mut Notes) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("SELECT id, fullname FROM voters WHERE voter_number IS NULL ORDER BY id")?;
+    let pending: Vec<(i64, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (id, fullname) in pending {
+        let number = new_voter_number(conn)?;
+        conn.execute("UPDATE voters SET voter_number=?1 WHERE id=?2", params![number, id])?;
+        notes.push(format!("voter #{id} '{fullname}' assigned voter number {number}"));
+    }
+    Ok(())
+}
+
+fn two_factor(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "admins", "totp_secret", "TEXT")?;
+    add_column_if_missing(conn, "admins", "totp_salt", "TEXT")?;
+    add_column_if_missing(conn, "admins", "totp_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "admins", "totp_last_step", "INTEGER")?;
+    conn.execute_batch(
+        r#"
+        -- Single-use two-factor recovery codes, stored hashed (see auth.rs).
+        CREATE TABLE IF NOT EXISTS admin_recovery_codes (
+            id INTEGER PRIMARY KEY,
+            admin_id INTEGER NOT NULL REFERENCES admins(id) ON DELETE CASCADE,
+            code_hash TEXT NOT NULL,
+            used_at TEXT
+        );
+    "#,
+    )
+}
+
+fn password_history(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    let had_password_history = table_exists(conn, "password_history");
+    conn.execute_batch(
+        r#"
+        -- Recent password and PIN hashes per account, to stop reuse (see auth.rs).
+        CREATE TABLE IF NOT EXISTS password_history (
+            id INTEGER PRIMARY KEY,
+            owner TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            set_at TEXT NOT NULL
+        );
+    "#,
+    )?;
+
+    // Seed history with the passwords and PINs in use, so they can't be "changed" to themselves.
+    if !had_password_history {
+        conn.execute_batch(
+            "INSERT INTO password_history (owner, hash, set_at)
+                 SELECT 'admin:' || id, password_hash, created_at FROM admins;
+             INSERT INTO password_history (owner, hash, set_at)
+                 SELECT 'voter:' || id, pinhash, strftime('%Y-%m-%dT%H:%M:%SZ', 'now') FROM voters;",
+        )?;
+    }
+    Ok(())
+}
+
+fn voting_tokens(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "voters", "pin_must_change", "INTEGER NOT NULL DEFAULT 0")?;
+    conn.execute_batch(
+        r#"
+        -- Single-use voting tokens issued at the polling station (see token.rs).
+        CREATE TABLE IF NOT EXISTS voting_tokens (
+            id INTEGER PRIMARY KEY,
+            voter_id INTEGER NOT NULL REFERENCES voters(id) ON DELETE CASCADE,
+            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
+            token_hash TEXT NOT NULL,
+            issued_by INTEGER REFERENCES admins(id),
+            issued_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            used_at TEXT,
+            revoked_at TEXT
+        );
+    "#,
+    )
+}
+
+fn districts(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+        -- Electoral districts and their polling stations (see district.rs).
+        CREATE TABLE IF NOT EXISTS districts (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS polling_stations (
+            id INTEGER PRIMARY KEY,
+            district_id INTEGER NOT NULL REFERENCES districts(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            UNIQUE(district_id, name)
+        );
+
+        -- Positions contested only in some districts. No rows: contested everywhere.
+        CREATE TABLE IF NOT EXISTS position_districts (
+            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
+            position_idx INTEGER NOT NULL,
+            district_id INTEGER NOT NULL REFERENCES districts(id) ON DELETE CASCADE,
+            PRIMARY KEY (election_id, position_idx, district_id)
+        );
+    "#,
+    )?;
+    add_column_if_missing(conn, "voters", "district_id", "INTEGER REFERENCES districts(id)")?;
+    add_column_if_missing(conn, "voters", "station_id", "INTEGER REFERENCES polling_stations(id)")?;
+    add_column_if_missing(conn, "ballots", "district_id", "INTEGER REFERENCES districts(id)")?;
+    add_column_if_missing(conn, "ballots", "station_id", "INTEGER REFERENCES polling_stations(id)")
+}
+
+fn poll_book(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "admins", "district_id", "INTEGER REFERENCES districts(id)")?;
+    add_column_if_missing(conn, "admins", "station_id", "INTEGER REFERENCES polling_stations(id)")?;
+    conn.execute_batch(
+        r#"
+        -- Electronic poll book: voters checked in by a district official.
+        -- Like participation, never linked to a ballot.
+        CREATE TABLE IF NOT EXISTS check_ins (
+            id INTEGER PRIMARY KEY,
+            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
+            voter_id INTEGER NOT NULL REFERENCES voters(id) ON DELETE CASCADE,
+            district_id INTEGER REFERENCES districts(id),
+            station_id INTEGER REFERENCES polling_stations(id),
+            checked_in_by INTEGER REFERENCES admins(id),
+            checked_in_at TEXT NOT NULL,
+            UNIQUE(election_id, voter_id)
+        );
+    "#,
+    )
+}
+
+fn offline_stations(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+        -- Offline polling stations (see station.rs). On a station database:
+        -- which station it is and the key it signs its results with.
+        CREATE TABLE IF NOT EXISTS station_identity (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            station_id INTEGER NOT NULL REFERENCES polling_stations(id),
+            public_key TEXT NOT NULL,
+            -- Sealed under the station passphrase (see sealing.rs)
+            secret_key TEXT NOT NULL,
+            secret_salt TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        -- On the central database: the key trusted for each station, the
+        -- result packages imported and the voters they report as having voted.
+        CREATE TABLE IF NOT EXISTS station_keys (
+            station_id INTEGER PRIMARY KEY REFERENCES polling_stations(id) ON DELETE CASCADE,
+            public_key TEXT NOT NULL,
+            trusted_by INTEGER REFERENCES admins(id),
+            trusted_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS station_results (
+            id INTEGER PRIMARY KEY,
+            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
+            station_id INTEGER NOT NULL REFERENCES polling_stations(id),
+            package_id TEXT NOT NULL UNIQUE,
+            payload TEXT NOT NULL,
+            ballot_count INTEGER NOT NULL,
+            imported_by INTEGER REFERENCES admins(id),
+            imported_at TEXT NOT NULL,
+            UNIQUE(election_id, station_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS station_participation (
+            election_id INTEGER NOT NULL,
+            voter_id INTEGER NOT NULL REFERENCES voters(id) ON DELETE CASCADE,
+            result_id INTEGER NOT NULL REFERENCES station_results(id) ON DELETE CASCADE,
+            PRIMARY KEY (election_id, voter_id)
+        );
+    "#,
+    )
+}
+
+/// Turnout is counted in the district a voter was in when they voted, not
+/// wherever they have moved since. Earlier participation can only be
+/// placed by the voter's current district.
+fn participation_district(conn: &Connection, _: &mut Notes) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE participation ADD COLUMN district_id INTEGER REFERENCES districts(id);
+        UPDATE participation SET district_id = (SELECT district_id FROM voters WHERE voters.id = participation.voter_id);
+    "#,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The schema released builds created at startup, before migrations.
+    const BASELINE_SCHEMA: &str = r#"
+        CREATE TABLE admins (id INTEGER PRIMARY KEY, username TEXT NOT NULL UNIQUE, password_hash TEXT NOT NULL, created_at TEXT NOT NULL);
+        CREATE TABLE elections (id INTEGER PRIMARY KEY, name TEXT NOT NULL, status TEXT NOT NULL DEFAULT 'Draft', created_at TEXT NOT NULL);
+        CREATE TABLE positions (id INTEGER PRIMARY KEY, election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE, idx INTEGER NOT NULL, title TEXT NOT NULL);
+        CREATE TABLE candidates (id INTEGER PRIMARY KEY, election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE, position_idx INTEGER NOT NULL, name TEXT NOT NULL, party TEXT NOT NULL);
+        CREATE TABLE voters (id INTEGER PRIMARY KEY, fullname TEXT NOT NULL, dob TEXT NOT NULL, pinhash TEXT NOT NULL);
+        CREATE TABLE votes (id INTEGER PRIMARY KEY, election_id INTEGER NOT NULL, voter_id INTEGER NOT NULL, position_idx INTEGER NOT NULL, candidate_id INTEGER NOT NULL, cast_at TEXT NOT NULL);
+
+        INSERT INTO elections VALUES (1, 'Council', 'Open', '2024-01-01T00:00:00Z');
+        INSERT INTO positions VALUES (1, 1, 1, 'Mayor'), (2, 1, 2, 'Treasurer');
+        INSERT INTO candidates VALUES (1, 1, 1, 'Ada', 'Blue'), (2, 1, 1, 'Ben', 'Red'), (3, 1, 2, 'Cy', 'Blue');
+        INSERT INTO voters VALUES (1, 'Alice', '1980-01-01', 'x'), (2, 'Bob', '1990-01-01', 'x');
+        INSERT INTO votes VALUES
+            (1, 1, 1, 1, 1, '2024-01-02T00:00:00Z'),
+            (2, 1, 1, 2, 3, '2024-01-02T00:00:00Z'),
+            (3, 1, 2, 1, 2, '2024-01-02T00:01:00Z');
+    "#;
+
+    #[test]
+    fn upgrades_a_baseline_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(BASELINE_SCHEMA).unwrap();
+        assert!(is_unversioned(&conn).unwrap());
+
+        let ran = migrate(&conn).unwrap();
+        assert_eq!(ran.len(), MIGRATIONS.len());
+        let notes: Vec<&String> = ran.iter().flat_map(|(_, notes)| notes).collect();
+        assert!(notes.iter().any(|n| n.as_str() == "2 ballot(s) separated from voter identities"));
+        assert_eq!(notes.iter().filter(|n| n.contains("assigned voter number")).count(), 2);
+
+        let (count, max): (u32, u32) = conn
+            .query_row("SELECT COUNT(*), MAX(version) FROM schema_version", [], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap();
+        assert_eq!(count as usize, MIGRATIONS.len());
+        assert_eq!(max, latest_version());
+
+        let voted: Vec<i64> = conn
+            .prepare("SELECT voter_id FROM participation WHERE election_id = 1 ORDER BY voter_id")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(voted, vec![1, 2]);
+
+        // Alice's two votes share a ballot; Bob's stands alone.
+        let ballot_of = |candidate: i64| -> i64 {
+            conn.query_row("SELECT ballot_id FROM votes WHERE candidate_id = ?1", [candidate], |r| r.get(0)).unwrap()
+        };
+        assert_eq!(ballot_of(1), ballot_of(3));
+        assert_ne!(ballot_of(1), ballot_of(2));
+        let ballots: i64 = conn.query_row("SELECT COUNT(*) FROM ballots WHERE election_id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(ballots, 2);
+
+        assert!(!table_exists(&conn, "legacy_votes"));
+        assert!(!column_exists(&conn, "votes", "voter_id").unwrap());
+        assert!(migrate(&conn).unwrap().is_empty());
+    }
+}
@@ -7,4 +7,305 @@
 // - Manage polling stations and local results
 // - Report local data to the central system
 // - Support the admin and voter modules during elections
+//
+// Every voter belongs to at most one district and, optionally, one of its
+// polling stations. A position with rows in `position_districts` is only
+// on the ballot in those districts; a position with none is contested
+// everywhere. Ballots record the district and station they were cast in
+// (never the voter), which is what the local breakdowns are counted from.
 // ============================================================
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{BallotMethod, District, DistrictTurnout, PollingStation, Position};
+use crate::vote::list_positions;
+
+pub fn add_district(conn: &Connection, name: &str) -> AppResult<i64> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Invalid("district name must not be empty".to_string()));
+    }
+    if find_district(conn, name).is_ok() {
+        return Err(AppError::Invalid(format!("district '{name}' already exists")));
+    }
+    conn.execute(
+        "INSERT INTO districts (name, created_at) VALUES (?1, ?2)",
+        params![name, Utc::now().to_rfc3339()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn add_station(conn: &Connection, district_id: i64, name: &str) -> AppResult<i64> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Invalid("polling station name must not be empty".to_string()));
+    }
+    if find_station(conn, district_id, name).is_ok() {
+        return Err(AppError::Invalid(format!("polling station '{name}' already exists in this district")));
+    }
+    conn.execute(
+        "INSERT INTO polling_stations (district_id, name) VALUES (?1, ?2)",
+        params![district_id, name],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Look a district up by name, ignoring case.
+pub fn find_district(conn: &Connection, name: &str) -> AppResult<i64> {
+    conn.query_row(
+        "SELECT id FROM districts WHERE LOWER(name)=LOWER(?1)",
+        params![name.trim()],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::NotFound(format!("district '{}'", name.trim())))
+}
+
+/// Look a polling station up by name within a district, ignoring case.
+pub fn find_station(conn: &Connection, district_id: i64, name: &str) -> AppResult<i64> {
+    conn.query_row(
+        "SELECT id FROM polling_stations WHERE district_id=?1 AND LOWER(name)=LOWER(?2)",
+        params![district_id, name.trim()],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::NotFound(format!("polling station '{}' in that district", name.trim())))
+}
+
+pub fn has_districts(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row("SELECT EXISTS(SELECT 1 FROM districts)", [], |row| row.get(0))
+}
+
+/// Resolve a district name and optional station name to their IDs.
+pub fn resolve(conn: &Connection, district: &str, station: Option<&str>) -> AppResult<(i64, Option<i64>)> {
+    let district_id = find_district(conn, district)?;
+    let station_id = station.map(|s| find_station(conn, district_id, s)).transpose()?;
+    Ok((district_id, station_id))
+}
+
+pub fn assign_voter(conn: &Connection, voter_id: i64, district_id: i64, station_id: Option<i64>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE voters SET district_id=?1, station_id=?2 WHERE id=?3",
+        params![district_id, station_id, voter_id],
+    )?;
+    Ok(())
+}
+
+pub fn voter_district(conn: &Connection, voter_id: i64) -> rusqlite::Result<Option<i64>> {
+    conn.query_row("SELECT district_id FROM voters WHERE id=?1", params![voter_id], |row| row.get(0))
+}
+
+/// Every district with its stations and number of assigned voters.
+pub fn list_districts(conn: &Connection) -> rusqlite::Result<Vec<District>> {
+    let mut stmt = conn.prepare(
+        "SELECT d.id, d.name, (SELECT COUNT(*) FROM voters v WHERE v.district_id = d.id)
+         FROM districts d ORDER BY d.name",
+    )?;
+    let mut districts: Vec<District> = stmt
+        .query_map([], |row| Ok(District { id: row.get(0)?, name: row.get(1)?, stations: Vec::new(), voters: row.get(2)? }))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut stmt = conn.prepare("SELECT id, district_id, name FROM polling_stations ORDER BY name")?;
+    let stations = stmt.query_map([], |row| Ok(PollingStation { id: row.get(0)?, district_id: row.get(1)?, name: row.get(2)? }))?;
+    for station in stations.filter_map(|r| r.ok()) {
+        if let Some(d) = districts.iter_mut().find(|d| d.id == station.district_id) {
+            d.stations.push(station);
+        }
+    }
+    Ok(districts)
+}
+
+/// Contest a position only in the given districts, replacing any earlier
+/// choice. An empty list makes it contested everywhere again.
+pub fn scope_position(conn: &Connection, election_id: i64, position_idx: i32, district_ids: &[i64]) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM position_districts WHERE election_id=?1 AND position_idx=?2",
+        params![election_id, position_idx],
+    )?;
+    for district_id in district_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO position_districts (election_id, position_idx, district_id) VALUES (?1, ?2, ?3)",
+            params![election_id, position_idx, district_id],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Districts each scoped position of an election is contested in.
+/// Positions contested everywhere are absent.
+pub fn position_scopes(conn: &Connection, election_id: i64) -> rusqlite::Result<HashMap<i32, Vec<i64>>> {
+    let mut stmt = conn.prepare("SELECT position_idx, district_id FROM position_districts WHERE election_id=?1")?;
+    let rows = stmt.query_map(params![election_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?)))?;
+    let mut scopes: HashMap<i32, Vec<i64>> = HashMap::new();
+    for (idx, district_id) in rows.filter_map(|r| r.ok()) {
+        scopes.entry(idx).or_default().push(district_id);
+    }
+    Ok(scopes)
+}
+
+/// The positions on a voter's ballot: those contested everywhere plus those
+/// contested in the voter's district.
+pub fn ballot_positions(conn: &Connection, election_id: i64, voter_id: i64) -> rusqlite::Result<Vec<Position>> {
+    district_positions(conn, election_id, voter_district(conn, voter_id)?)
+}
+
+/// Positions contested in `district`; with no district, only those
+/// contested everywhere.
+pub fn district_positions(conn: &Connection, election_id: i64, district: Option<i64>) -> rusqlite::Result<Vec<Position>> {
+    let scopes = position_scopes(conn, election_id)?;
+    Ok(list_positions(conn, election_id)?
+        .into_iter()
+        .filter(|p| match scopes.get(&p.idx) {
+            None => true,
+            Some(districts) => district.is_some_and(|d| districts.contains(&d)),
+        })
+        .collect())
+}
+
+/// The district (and station, if any) an official works at.
+pub fn official_placement(conn: &Connection, admin_id: i64) -> rusqlite::Result<(Option<i64>, Option<i64>)> {
+    conn.query_row(
+        "SELECT district_id, station_id FROM admins WHERE id=?1",
+        params![admin_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+pub fn assign_official(conn: &Connection, admin_id: i64, district_id: i64, station_id: Option<i64>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE admins SET district_id=?1, station_id=?2 WHERE id=?3",
+        params![district_id, station_id, admin_id],
+    )?;
+    Ok(())
+}
+
+pub fn district_name(conn: &Connection, district_id: i64) -> rusqlite::Result<String> {
+    conn.query_row("SELECT name FROM districts WHERE id=?1", params![district_id], |row| row.get(0))
+}
+
+pub fn station_name(conn: &Connection, station_id: i64) -> rusqlite::Result<String> {
+    conn.query_row("SELECT name FROM polling_stations WHERE id=?1", params![station_id], |row| row.get(0))
+}
+
+/// The district and station a voter's ballot is recorded under.
+pub fn voter_placement(conn: &Connection, voter_id: i64) -> rusqlite::Result<(Option<i64>, Option<i64>)> {
+    conn.query_row(
+        "SELECT district_id, station_id FROM voters WHERE id=?1",
+        params![voter_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Where ballots are grouped for a local breakdown.
+#[derive(Debug, Clone, Copy)]
+pub enum Area {
+    District,
+    Station,
+}
+
+/// What a vote row counts for in a breakdown: first preferences for ranked
+/// methods, points for score, one mark otherwise. Matches the election-wide
+/// "votes" and "points" totals in view-results.
+fn mark_sum(method: BallotMethod) -> &'static str {
+    match method {
+        BallotMethod::Score => "COALESCE(v.score, 0)",
+        _ => "CASE WHEN v.rank IS NULL OR v.rank = 1 THEN 1 ELSE 0 END",
+    }
+}
+
+/// Marks per candidate for a position, election-wide, straight from the
+/// vote rows.
+pub fn position_totals(conn: &Connection, election_id: i64, position: &Position) -> rusqlite::Result<HashMap<i64, u64>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT v.candidate_id, SUM({}) FROM votes v
+         WHERE v.election_id=?1 AND v.position_idx=?2 GROUP BY v.candidate_id",
+        mark_sum(position.rules.method)
+    ))?;
+    let rows = stmt.query_map(params![election_id, position.idx], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)? as u64)))?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Marks per candidate for a position, split by the district or station
+/// each ballot was cast in (None: no district/station recorded).
+pub fn position_totals_by(
+    conn: &Connection,
+    election_id: i64,
+    position: &Position,
+    area: Area,
+) -> rusqlite::Result<HashMap<Option<i64>, HashMap<i64, u64>>> {
+    let column = match area {
+        Area::District => "b.district_id",
+        Area::Station => "b.station_id",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {column}, v.candidate_id, SUM({}) FROM votes v JOIN ballots b ON b.id = v.ballot_id
+         WHERE v.election_id=?1 AND v.position_idx=?2 GROUP BY {column}, v.candidate_id",
+        mark_sum(position.rules.method)
+    ))?;
+    let rows = stmt.query_map(params![election_id, position.idx], |row| {
+        Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)? as u64))
+    })?;
+    let mut totals: HashMap<Option<i64>, HashMap<i64, u64>> = HashMap::new();
+    for (area_id, candidate_id, marks) in rows.filter_map(|r| r.ok()) {
+        totals.entry(area_id).or_default().insert(candidate_id, marks);
+    }
+    Ok(totals)
+}
+
+/// Ballots cast at each polling station in an election.
+pub fn station_ballots(conn: &Connection, election_id: i64) -> rusqlite::Result<HashMap<i64, u64>> {
+    let mut stmt = conn.prepare(
+        "SELECT station_id, COUNT(*) FROM ballots WHERE election_id=?1 AND station_id IS NOT NULL GROUP BY station_id",
+    )?;
+    let rows = stmt.query_map(params![election_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)? as u64)))?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Turnout for every district against its registered roll, plus a row for
+/// voters and ballots with no district if there are any. Voters are counted
+/// as having voted in the district they were in when they voted, or of the
+/// offline station whose results reported them.
+pub fn turnout(conn: &Connection, election_id: i64) -> rusqlite::Result<Vec<DistrictTurnout>> {
+    let mut stmt = conn.prepare(
+        "SELECT d.id, d.name,
+            (SELECT COUNT(*) FROM voters v WHERE v.district_id = d.id),
+            (SELECT COUNT(*) FROM participation p WHERE p.election_id = ?1 AND p.district_id = d.id)
+              + (SELECT COUNT(*) FROM station_participation sp JOIN station_results r ON r.id = sp.result_id
+                    JOIN polling_stations s ON s.id = r.station_id
+                 WHERE sp.election_id = ?1 AND s.district_id = d.id),
+            (SELECT COUNT(*) FROM ballots b WHERE b.election_id = ?1 AND b.district_id = d.id)
+         FROM districts d ORDER BY d.name",
+    )?;
+    let row_to_turnout = |row: &rusqlite::Row| {
+        Ok(DistrictTurnout {
+            district_id: row.get(0)?,
+            name: row.get(1)?,
+            registered: row.get::<_, i64>(2)? as u64,
+            voted: row.get::<_, i64>(3)? as u64,
+            ballots: row.get::<_, i64>(4)? as u64,
+        })
+    };
+    let mut rows: Vec<DistrictTurnout> = stmt
+        .query_map(params![election_id], row_to_turnout)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let unassigned = conn.query_row(
+        "SELECT NULL, '(no district)',
+            (SELECT COUNT(*) FROM voters WHERE district_id IS NULL),
+            (SELECT COUNT(*) FROM participation WHERE election_id = ?1 AND district_id IS NULL),
+            (SELECT COUNT(*) FROM ballots WHERE election_id = ?1 AND district_id IS NULL)",
+        params![election_id],
+        row_to_turnout,
+    )?;
+    if unassigned.registered > 0 || unassigned.ballots > 0 {
+        rows.push(unassigned);
+    }
+    Ok(rows)
+}
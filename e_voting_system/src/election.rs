@@ -9,88 +9,92 @@
 // - Compute and display election results
 // ============================================================
 
-// E-Voting System: District Officials Module
+use rusqlite::{params, Connection, OptionalExtension};
 
-use rusqlite::{params, Connection, Result};// this part allows to import directly from rusqlite crate which is used by SQlite database
-use std::collections::HashMap;
+use crate::error::{AppError, AppResult};
+use crate::models::{Election, ElectionStatus};
 
-/// Represents a basic election record.
-#[derive(Debug)]
-// First we setup a small SQLite darabase
-fn setup_database(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS elections (
-            id INTEGER PRIMARY KEY,
-            title TEXT NOT NULL,
-            status TEXT NOT NULL
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS votes (
-            id INTEGER PRIMARY KEY,
-            election_id INTEGER,
-            position_id INTEGER,
-            candidate_id INTEGER
-        )",
-        [],
-    )?;
-
-    // Add a demo election if not already present
-    conn.execute(
-        "INSERT OR IGNORE INTO elections (id, title, status)
-         VALUES (1, 'General Election 2025', 'Draft')",
-        [],
-    )?;
+/// Fetch the current status of an election.
+pub fn election_status(conn: &Connection, election_id: i64) -> AppResult<ElectionStatus> {
+    conn.query_row(
+        "SELECT status FROM elections WHERE id=?1",
+        params![election_id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::NotFound(format!("election #{election_id}")))
+}
 
-    // Add some sample votes
-    conn.execute("DELETE FROM votes", [])?;// foe better security we delete old vote if it exists
-    let sample_votes = vec![               // Basically there are three toples election_id, position_id and candidate_id 
-        (1, 101, 201),                     // Two votes for candidate 201 in position 101 of election 1  
-        (1, 101, 201),
-        (1, 101, 202),
-        (1, 102, 203),
-        (1, 102, 203),
-        (1, 102, 204),
-    ];
+/// Every election with its status, for listings that need no login.
+pub fn list_elections(conn: &Connection) -> rusqlite::Result<Vec<Election>> {
+    let mut stmt = conn.prepare("SELECT id, name, status, created_at FROM elections")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Election {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            status: row.get(2)?,
+            created_at: row.get(3)?,
+        })
+    })?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
 
-   for (election_id, position_id, candidate_id) in sample_votes {
-        conn.execute(
-            "INSERT INTO votes (election_id, position_id, candidate_id)
-             VALUES (?1, ?2, ?3)",        // Uses ?1, ?2, ?3 placeholders and params![] for safe insertion
-            params![election_id, position_id, candidate_id],
-        )?;
+/// Refuse `action` unless the election is in one of the `allowed` statuses.
+pub fn require_status(
+    conn: &Connection,
+    election_id: i64,
+    allowed: &[ElectionStatus],
+    action: &'static str,
+) -> AppResult<ElectionStatus> {
+    let status = election_status(conn, election_id)?;
+    if allowed.contains(&status) {
+        Ok(status)
+    } else {
+        Err(AppError::WrongStatus { election_id, status, action })
     }
-
-    Ok(())
-}  
-struct Election {
-    id: i32,
-    title: String,
-    status: String,
 }
 
-
-/// Opens an election so voters can start casting ballots.
-fn open_election(conn: &Connection, election_id: i32) -> Result<()> {
-    conn.execute(
-        "UPDATE elections SET status = 'Open' WHERE id = ?1 AND status != 'Open'",
-        params![election_id],
+/// Move an election to `next`, refusing any transition the state machine
+/// does not allow. The update is conditional on the status we read so two
+/// concurrent transitions cannot both succeed.
+pub fn transition(conn: &Connection, election_id: i64, next: ElectionStatus) -> AppResult<()> {
+    let current = election_status(conn, election_id)?;
+    if !current.can_transition_to(next) {
+        return Err(AppError::InvalidTransition { election_id, from: current, to: next });
+    }
+    let changed = conn.execute(
+        "UPDATE elections SET status=?1 WHERE id=?2 AND status=?3",
+        params![next, election_id, current],
     )?;
-    println!("Election {} is now OPEN.", election_id);
+    if changed == 0 {
+        // Someone else changed the status between our read and write.
+        let now = election_status(conn, election_id)?;
+        return Err(AppError::InvalidTransition { election_id, from: now, to: next });
+    }
     Ok(())
 }
-/// Here it closes an ongoing election to stop further voting.
-fn close_election(conn: &Connection, election_id: i32) -> Result<()> {
-    conn.execute(
-        "UPDATE elections SET status = 'Closed' WHERE id = ?1 AND status = 'Open'",
-        params![election_id],
-    )?;
-    println!("Election {} has been CLOSED.", election_id);
-    Ok(())
+
+/// Opens an election so voters can start casting ballots (also resumes a suspended one).
+pub fn open_election(conn: &Connection, election_id: i64) -> AppResult<()> {
+    transition(conn, election_id, ElectionStatus::Open)
 }
 
+/// Closes an ongoing election to stop further voting.
+pub fn close_election(conn: &Connection, election_id: i64) -> AppResult<()> {
+    transition(conn, election_id, ElectionStatus::Closed)
+}
 
+/// Temporarily halts voting on an open election.
+pub fn suspend_election(conn: &Connection, election_id: i64) -> AppResult<()> {
+    transition(conn, election_id, ElectionStatus::Suspended)
+}
 
+/// Abandons an election that has not been closed yet.
+pub fn cancel_election(conn: &Connection, election_id: i64) -> AppResult<()> {
+    transition(conn, election_id, ElectionStatus::Cancelled)
+}
 
+/// Marks the results of a closed election as final.
+pub fn certify_election(conn: &Connection, election_id: i64) -> AppResult<()> {
+    transition(conn, election_id, ElectionStatus::Certified)
+}
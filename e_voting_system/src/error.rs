@@ -0,0 +1,88 @@
+// ============================================================
+// File: error.rs
+// Purpose: Shared error type for operations that can be refused
+//          for reasons other than a database failure.
+// ============================================================
+
+use std::fmt;
+
+use crate::models::{ElectionStatus, Permission};
+
+#[derive(Debug)]
+pub enum AppError {
+    /// Underlying SQLite failure
+    Db(rusqlite::Error),
+    /// The requested election/candidate/etc. does not exist
+    NotFound(String),
+    /// Input that is well-formed but not acceptable
+    Invalid(String),
+    /// A lifecycle transition that the state machine does not allow
+    InvalidTransition {
+        election_id: i64,
+        from: ElectionStatus,
+        to: ElectionStatus,
+    },
+    /// The election is not in a status that permits the operation
+    WrongStatus {
+        election_id: i64,
+        status: ElectionStatus,
+        action: &'static str,
+    },
+    /// The voter already has a ballot in this election
+    AlreadyVoted { election_id: i64 },
+    /// No valid admin session: not logged in, expired, revoked or bad credentials
+    Unauthenticated(String),
+    /// Logged in, but none of the admin's roles grants the permission
+    PermissionDenied { username: String, permission: Permission },
+    /// Password was right but the account needs a two-factor code too
+    TwoFactorRequired,
+    /// Too many failed logins for the account or terminal; wait this long
+    LockedOut { seconds: i64 },
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Db(e) => write!(f, "database error: {e}"),
+            AppError::NotFound(what) => write!(f, "{what} not found"),
+            AppError::Invalid(why) => write!(f, "{why}"),
+            AppError::InvalidTransition { election_id, from, to } => write!(
+                f,
+                "election #{election_id} cannot move from {from} to {to}"
+            ),
+            AppError::WrongStatus { election_id, status, action } => write!(
+                f,
+                "cannot {action}: election #{election_id} is {status}"
+            ),
+            AppError::AlreadyVoted { election_id } => write!(
+                f,
+                "a ballot has already been cast for election #{election_id}"
+            ),
+            AppError::Unauthenticated(why) => write!(f, "{why}"),
+            AppError::PermissionDenied { username, permission } => write!(
+                f,
+                "permission denied: '{username}' has no role that may {permission}"
+            ),
+            AppError::TwoFactorRequired => write!(f, "a two-factor code is required for this account"),
+            AppError::LockedOut { seconds } if *seconds > 90 => write!(
+                f,
+                "too many failed attempts; try again in {} minutes",
+                (seconds + 59) / 60
+            ),
+            AppError::LockedOut { seconds } => write!(
+                f,
+                "too many failed attempts; try again in {seconds} seconds"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Db(e)
+    }
+}
@@ -0,0 +1,139 @@
+// ============================================================
+// File: lockout.rs
+// Purpose: Slows down and locks out repeated failed logins.
+//
+// Responsibilities:
+// - Count failed admin and voter logins per account and per terminal
+// - Enforce an exponential back-off between attempts
+// - Lock an account or terminal once its threshold is reached
+// - Let admins list and clear lockouts
+//
+// Counters live in `login_throttle`, keyed "admin:<username>",
+// "voter:<login>" or "terminal:<id>". Thresholds and timings come from
+// config.rs. Failures older than the lockout window are forgotten.
+// ============================================================
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::audit;
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::models::Lockout;
+
+/// Identifies the machine a login comes from. Set `RUSTTRUST_TERMINAL` on
+/// polling-station machines; otherwise the host name is used.
+pub fn terminal_id() -> String {
+    ["RUSTTRUST_TERMINAL", "HOSTNAME"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| "local".to_string())
+}
+
+pub fn account_key(kind: &str, login: &str) -> String {
+    format!("{kind}:{}", login.trim().to_lowercase())
+}
+
+pub fn terminal_key() -> String {
+    format!("terminal:{}", terminal_id())
+}
+
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Refuse the attempt if any of `keys` is backing off or locked.
+pub fn check(conn: &Connection, keys: &[String]) -> AppResult<()> {
+    let now = Utc::now();
+    for key in keys {
+        let until: Option<String> = conn
+            .query_row("SELECT locked_until FROM login_throttle WHERE key=?1", params![key], |row| row.get(0))
+            .optional()?
+            .flatten();
+        if let Some(until) = until.as_deref().and_then(parse_time) {
+            if until > now {
+                return Err(AppError::LockedOut { seconds: (until - now).num_seconds() + 1 });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Count a failed attempt against every key and set how long each must wait.
+pub fn record_failure(conn: &Connection, keys: &[String]) -> AppResult<()> {
+    let window = Duration::minutes(config::get(conn, "lockout.minutes")?);
+    let backoff = config::get(conn, "lockout.backoff_seconds")?;
+    let now = Utc::now();
+
+    for key in keys {
+        let threshold = if key.starts_with("terminal:") {
+            config::get(conn, "lockout.terminal_threshold")?
+        } else {
+            config::get(conn, "lockout.account_threshold")?
+        };
+        let previous: Option<(i64, String)> = conn
+            .query_row(
+                "SELECT failures, last_failure_at FROM login_throttle WHERE key=?1",
+                params![key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let failures = match previous {
+            Some((n, last)) if parse_time(&last).is_some_and(|t| now - t < window) => n + 1,
+            _ => 1,
+        };
+
+        let wait = if failures >= threshold {
+            window
+        } else {
+            // 2^(failures-1) doublings, capped well below overflow and at the window.
+            Duration::seconds(backoff << (failures - 1).min(20)).min(window)
+        };
+        let locked_until = (wait > Duration::zero()).then(|| (now + wait).to_rfc3339());
+        conn.execute(
+            "INSERT INTO login_throttle (key, failures, last_failure_at, locked_until) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(key) DO UPDATE SET failures=excluded.failures,
+                 last_failure_at=excluded.last_failure_at, locked_until=excluded.locked_until",
+            params![key, failures, now.to_rfc3339(), locked_until],
+        )?;
+        if failures == threshold {
+            audit::record(conn, "system", "lockout", &format!("{key} locked for {} min after {failures} failures", window.num_minutes()))?;
+        }
+    }
+    Ok(())
+}
+
+/// A successful login clears the account's counter. Terminal counters are
+/// left to expire so one valid login cannot reset a guessing run.
+pub fn record_success(conn: &Connection, account_key: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM login_throttle WHERE key=?1", params![account_key])?;
+    Ok(())
+}
+
+/// Every account or terminal with recent failures, most failures first.
+pub fn list(conn: &Connection) -> rusqlite::Result<Vec<Lockout>> {
+    let now = Utc::now();
+    let mut stmt = conn.prepare(
+        "SELECT key, failures, last_failure_at, locked_until FROM login_throttle ORDER BY failures DESC, key",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let locked_until: Option<String> = row.get(3)?;
+        let locked = locked_until.as_deref().and_then(parse_time).is_some_and(|t| t > now);
+        Ok(Lockout {
+            key: row.get(0)?,
+            failures: row.get(1)?,
+            last_failure_at: row.get(2)?,
+            locked_until: if locked { locked_until } else { None },
+        })
+    })?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Forget the failures for one key, or for all of them.
+pub fn clear(conn: &Connection, key: Option<&str>) -> rusqlite::Result<usize> {
+    match key {
+        Some(key) => conn.execute("DELETE FROM login_throttle WHERE key=?1", params![key]),
+        None => conn.execute("DELETE FROM login_throttle", []),
+    }
+}
@@ -1,10 +1,23 @@
 // src/main.rs - Entry point for the Secure Voting Machine 
 
 mod admin;
+mod audit;
+mod config;
+mod db;
+mod district;
 mod models;
 mod auth;
 mod voter;
 mod vote;
+mod election;
+mod error;
+mod lockout;
+mod policy;
+mod sealing;
+mod station;
+mod tally;
+mod token;
+mod totp;
 
 use clap::{Parser, Subcommand, Args};
 use rusqlite::{params, Connection};
@@ -12,7 +25,8 @@ use chrono::Utc;
 use std::path::PathBuf;
 use std::io::{self, Write};
 use crate::admin::AdminService;
-use crate::auth::{hash_password, verify_password};
+use crate::auth::hash_new_password;
+use crate::models::{BallotMethod, BallotRules, PassThreshold, Role};
 use crate::voter::{voter_login, voter_portal};
 
 // --------------------------- CLI STRUCTS ---------------------------
@@ -44,10 +58,64 @@ enum Commands {
     /// Simple test to list all elections
     List,
 
+    /// Check that a ballot tracking code is in the published ballot list
+    VerifyReceipt {
+        code: String,
+    },
+
+    /// Print the published ballot list (tracking codes and fingerprints) for an election
+    PublishedBallots {
+        election_id: i64,
+    },
+
+    /// Inspect the tamper-evident audit log
+    Audit(AuditCmd),
+
+    /// Database maintenance
+    Db(DbCmd),
+
     /// Launch interactive menu
     Menu,
 }
 
+// --------------------------- Audit CLI -----------------------------
+
+#[derive(Args, Debug)]
+struct AuditCmd {
+    #[command(subcommand)]
+    sub: AuditSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum AuditSub {
+    /// Check the hash chain and report modified or deleted entries
+    Verify,
+
+    /// Show the most recent entries
+    Show {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: u32,
+    },
+}
+
+// --------------------------- Db CLI --------------------------------
+
+#[derive(Args, Debug)]
+struct DbCmd {
+    #[command(subcommand)]
+    sub: DbSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum DbSub {
+    /// Apply pending schema migrations (they also run before every command)
+    Migrate {
+        /// Only list applied and pending migrations; change nothing
+        #[arg(long)]
+        status: bool,
+    },
+}
+
 // --------------------------- Admin CLI -----------------------------
 
 #[derive(Args, Debug)]
@@ -65,6 +133,74 @@ enum AdminSub {
         positions: String,
     },
 
+    /// Set how a position is voted and counted (plurality, ranked, stv, approval, score)
+    SetBallotMethod {
+        election_id: i64,
+        position_idx: i32,
+        method: BallotMethod,
+
+        /// Number of seats to fill (STV only)
+        #[arg(long, default_value_t = 1)]
+        seats: u32,
+
+        /// Fewest candidates a voter must mark or rank (not plurality)
+        #[arg(long, default_value_t = 0)]
+        min_selections: u32,
+
+        /// Most candidates a voter may mark or rank, 0 for no limit (not plurality)
+        #[arg(long, default_value_t = 0)]
+        max_selections: u32,
+
+        /// Highest score per candidate (score only)
+        #[arg(long, default_value_t = 5)]
+        max_score: u32,
+
+        /// Let voters write in a candidate (plurality only)
+        #[arg(long)]
+        allow_write_in: bool,
+    },
+
+    /// List write-ins awaiting adjudication
+    WriteIns {
+        election_id: i64,
+    },
+
+    /// Count a write-in (and identical pending ones) for an existing candidate
+    MergeWriteIn {
+        write_in_id: i64,
+        candidate_id: i64,
+    },
+
+    /// Create a new candidate from a write-in (and count identical pending ones)
+    AcceptWriteIn {
+        write_in_id: i64,
+        #[arg(long, default_value = "Write-in")]
+        party: String,
+    },
+
+    /// Reject a write-in (and identical pending ones)
+    RejectWriteIn {
+        write_in_id: i64,
+    },
+
+    /// Add a ballot question (referendum) to a draft election
+    AddQuestion {
+        election_id: i64,
+        text: String,
+
+        /// Comma-separated answer choices
+        #[arg(long, default_value = "Yes,No")]
+        options: String,
+
+        /// Share an option needs to carry: majority or two-thirds
+        #[arg(long, default_value = "majority")]
+        threshold: PassThreshold,
+
+        /// Percentage of registered voters who must answer (0 for none)
+        #[arg(long, default_value_t = 0)]
+        quorum: u32,
+    },
+
     /// Add a candidate
     AddCandidate {
         election_id: i64,
@@ -78,6 +214,25 @@ enum AdminSub {
         fullname: String,
         dob: String,
         pin: String,
+
+        /// District the voter belongs to (required once any district exists; a district official's own by default)
+        #[arg(long)]
+        district: Option<String>,
+
+        /// Polling station within the district
+        #[arg(long, requires = "district")]
+        station: Option<String>,
+    },
+
+    /// Give a voter a random temporary PIN they must change at next login
+    ResetVoterPin {
+        voter_number: String,
+    },
+
+    /// Issue a single-use voting token to a voter checked in at the polling station
+    IssueToken {
+        voter_number: String,
+        election_id: i64,
     },
 
     /// View election results
@@ -85,13 +240,301 @@ enum AdminSub {
         election_id: i64,
     },
 
-    /// Log in as an admin
+    /// Break results down by district and polling station, with turnout and
+    /// a reconciliation of district subtotals against the overall totals
+    DistrictResults {
+        election_id: i64,
+    },
+
+    /// On a station: sign the results of its closed local box for central
+    ExportStationResults {
+        election_id: i64,
+
+        /// Passphrase given to `admin station init`
+        #[arg(short = 'p', long)]
+        passphrase: String,
+
+        /// Where to write the package (default: station-results-<election>-<package>.json)
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+    },
+
+    /// On central: verify a station's signed result package and record it
+    ImportStationResults {
+        file: PathBuf,
+    },
+
+    /// Open an election for voting (or resume a suspended one)
+    OpenElection {
+        election_id: i64,
+    },
+
+    /// Close an open election so no further ballots are accepted
+    CloseElection {
+        election_id: i64,
+    },
+
+    /// Temporarily suspend voting on an open election
+    SuspendElection {
+        election_id: i64,
+    },
+
+    /// Cancel an election that has not been closed
+    CancelElection {
+        election_id: i64,
+    },
+
+    /// Certify the results of a closed election
+    Certify {
+        election_id: i64,
+    },
+
+    /// Log in as an admin; later admin commands use the saved session
     Login {
         #[arg(short = 'u', long)]
         username: String,
 
         #[arg(short = 'p', long)]
         password: String,
+
+        /// Authenticator code or recovery code, once two-factor is enabled
+        #[arg(long)]
+        otp: Option<String>,
+
+        /// How long the session lasts
+        #[arg(long, default_value_t = auth::DEFAULT_SESSION_MINUTES)]
+        ttl_minutes: i64,
+    },
+
+    /// End the current session and forget its token
+    Logout,
+
+    /// Show who the current session belongs to and when it expires
+    Whoami,
+
+    /// Revoke every open session of an admin
+    RevokeSessions {
+        username: String,
+    },
+
+    /// Give an admin a role (super-admin, election-admin, district-official, auditor)
+    GrantRole {
+        username: String,
+        role: Role,
+    },
+
+    /// Take a role away from an admin
+    RevokeRole {
+        username: String,
+        role: Role,
+    },
+
+    /// List admins and their roles
+    Roles,
+
+    /// Manage admin accounts
+    User(UserCmd),
+
+    /// List accounts and terminals with recent failed logins
+    Lockouts,
+
+    /// Clear failed logins for a key such as "voter:7kq2-m9xd" or "terminal:booth-3"
+    ClearLockout {
+        key: Option<String>,
+
+        /// Clear every lockout
+        #[arg(long, conflicts_with = "key")]
+        all: bool,
+    },
+
+    /// Show or change security settings
+    Config(ConfigCmd),
+
+    /// Set up or remove two-factor login (authenticator app codes)
+    Totp(TotpCmd),
+
+    /// Manage districts, polling stations and voter assignment
+    District(DistrictCmd),
+
+    /// Offline polling stations: signing keys and imported results
+    Station(StationCmd),
+}
+
+#[derive(Args, Debug)]
+struct StationCmd {
+    #[command(subcommand)]
+    sub: StationSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum StationSub {
+    /// Run this database as an offline polling station and create its signing key
+    Init {
+        district: String,
+        station: String,
+
+        /// Passphrase the signing key is encrypted with; needed for every export
+        #[arg(short = 'p', long)]
+        passphrase: String,
+    },
+
+    /// Show which station this database is and its public key
+    Show,
+
+    /// On central: trust a station's public key for its result packages
+    Trust {
+        district: String,
+        station: String,
+        public_key: String,
+    },
+
+    /// On central: list imported station packages and their combined tallies
+    Results {
+        election_id: i64,
+    },
+}
+
+#[derive(Args, Debug)]
+struct DistrictCmd {
+    #[command(subcommand)]
+    sub: DistrictSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum DistrictSub {
+    /// Create a district
+    Add {
+        name: String,
+
+        /// Comma-separated polling stations to create with it
+        #[arg(long, value_delimiter = ',')]
+        stations: Vec<String>,
+    },
+
+    /// Add a polling station to a district
+    AddStation {
+        district: String,
+        name: String,
+    },
+
+    /// List districts, their polling stations and voter counts
+    List,
+
+    /// Move a voter to a district and optional polling station
+    Assign {
+        voter_number: String,
+        district: String,
+
+        #[arg(long)]
+        station: Option<String>,
+    },
+
+    /// Set the district (and station) whose poll book an official keeps
+    AssignOfficial {
+        username: String,
+        district: String,
+
+        #[arg(long)]
+        station: Option<String>,
+    },
+
+    /// Contest a position only in some districts (no districts: everywhere)
+    ScopePosition {
+        election_id: i64,
+        position_idx: i32,
+
+        /// Comma-separated district names
+        #[arg(long, value_delimiter = ',')]
+        districts: Vec<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct TotpCmd {
+    #[command(subcommand)]
+    sub: TotpSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum TotpSub {
+    /// Generate a secret and recovery codes for your account
+    Enroll {
+        #[arg(short = 'p', long)]
+        password: String,
+    },
+
+    /// Turn two-factor on with a code from the authenticator app
+    Confirm {
+        code: String,
+
+        #[arg(short = 'p', long)]
+        password: String,
+    },
+
+    /// Remove two-factor from your account, or another admin's
+    Disable {
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct ConfigCmd {
+    #[command(subcommand)]
+    sub: ConfigSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigSub {
+    /// List every setting with its current value
+    Show,
+
+    /// Change a setting
+    Set {
+        key: String,
+        value: i64,
+    },
+}
+
+#[derive(Args, Debug)]
+struct UserCmd {
+    #[command(subcommand)]
+    sub: UserSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum UserSub {
+    /// Create an admin account
+    Add {
+        username: String,
+
+        #[arg(short = 'p', long)]
+        password: String,
+
+        /// Comma-separated roles to grant (super-admin, election-admin, district-official, auditor)
+        #[arg(long, value_delimiter = ',', default_value = "election-admin")]
+        roles: Vec<Role>,
+    },
+
+    /// List admin accounts, their roles and who created them
+    List,
+
+    /// Stop an admin from logging in and end their sessions
+    Disable {
+        username: String,
+    },
+
+    /// Let a disabled admin log in again
+    Enable {
+        username: String,
+    },
+
+    /// Set a new password for an admin and end their sessions
+    ResetPassword {
+        username: String,
+
+        #[arg(short = 'p', long)]
+        password: String,
     },
 }
 
@@ -115,7 +558,8 @@ fn show_main_menu() {
     println!("2. Admin Operations");
     println!("3. Voter Login");
     println!("4. List Elections");
-    println!("5. Exit");
+    println!("5. District Official Console");
+    println!("6. Exit");
     println!("=====================================");
 }
 
@@ -125,17 +569,104 @@ fn show_admin_menu() {
     println!("2. Add Candidate");
     println!("3. Register Voter");
     println!("4. View Election Results");
-    println!("5. Login as Admin");
-    println!("6. Back to Main Menu");
+    println!("5. Open Election");
+    println!("6. Close Election");
+    println!("7. Suspend Election");
+    println!("8. Cancel Election");
+    println!("9. Certify Election");
+    println!("10. Set Ballot Method");
+    println!("11. Add Ballot Question");
+    println!("12. Review Write-ins");
+    println!("13. Logout");
+    println!("14. Back to Main Menu");
     println!("=================================");
 }
 
+fn interactive_transition<'a>(
+    admin: &AdminService<'a>,
+    label: &str,
+    op: fn(&AdminService<'a>, i64) -> error::AppResult<()>,
+) {
+    let election_id = read_input("Enter election ID: ");
+    match election_id.parse::<i64>() {
+        Ok(eid) => match op(admin, eid) {
+            Ok(_) => println!("✅ Election #{} {}.", eid, label),
+            Err(e) => println!("❌ Error: {}", e),
+        },
+        _ => println!("❌ Invalid election ID"),
+    }
+}
+
+fn interactive_write_in_review(admin: &AdminService, eid: i64) {
+    match admin.write_in_queue(eid) {
+        Ok(queue) if queue.is_empty() => println!("No write-ins awaiting review."),
+        Ok(queue) => {
+            print_write_in_queue(&queue);
+            let wid = read_input("Enter write-in ID to resolve (blank to skip): ");
+            if let Ok(wid) = wid.parse::<i64>() {
+                let action = read_input("(m)erge into candidate, (c)reate candidate, or (r)eject: ");
+                let result = match action.to_ascii_lowercase().as_str() {
+                    "m" => match read_input("Enter candidate ID: ").parse::<i64>() {
+                        Ok(cid) => admin.merge_write_in(wid, cid).map(|n| format!("{} write-in(s) counted for candidate #{}", n, cid)),
+                        Err(_) => Err(error::AppError::Invalid("invalid candidate ID".to_string())),
+                    },
+                    "c" => {
+                        let party = read_input("Enter party [Write-in]: ");
+                        let party = if party.is_empty() { "Write-in".to_string() } else { party };
+                        admin.accept_write_in(wid, &party).map(|(cid, n)| format!("Candidate #{} created; {} write-in(s) counted", cid, n))
+                    }
+                    "r" => admin.reject_write_in(wid).map(|n| format!("{} write-in(s) rejected", n)),
+                    _ => Err(error::AppError::Invalid("unknown action".to_string())),
+                };
+                match result {
+                    Ok(msg) => println!("✅ {}", msg),
+                    Err(e) => println!("❌ Error: {}", e),
+                }
+            }
+        }
+        Err(e) => println!("❌ Error listing write-ins: {}", e),
+    }
+}
+
+/// Prompt for admin credentials (and a two-factor code when the account
+/// needs one). Returns the session token, or None if login failed.
+fn interactive_login(conn: &Connection, title: &str) -> Option<String> {
+    println!("\n🔐 {}", title);
+    let username = read_input("Enter admin username: ");
+    let password = read_input("Enter admin password: ");
+    let mut login = auth::login_admin(conn, &username, &password, None, auth::DEFAULT_SESSION_MINUTES);
+    if let Err(error::AppError::TwoFactorRequired) = login {
+        let code = read_input("Enter authenticator code (or a recovery code): ");
+        login = auth::login_admin(conn, &username, &password, Some(&code), auth::DEFAULT_SESSION_MINUTES);
+    }
+    match login {
+        Ok((token, session)) => {
+            println!("✅ Admin '{}' successfully logged in! Session expires {}", session.username, session.expires_at);
+            Some(token)
+        }
+        Err(e) => {
+            println!("❌ {}", e);
+            None
+        }
+    }
+}
+
 fn interactive_admin_operations(conn: &Connection) {
-    let admin = AdminService::new(conn);
-    
+    let Some(token) = interactive_login(conn, "Admin Login") else { return };
+
     loop {
+        // Re-checked every time so expiry and revocation take effect mid-menu.
+        let session = match auth::validate_session(conn, &token) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("❌ {}", e);
+                break;
+            }
+        };
+        let admin = AdminService::new(conn, &session);
+
         show_admin_menu();
-        let choice = read_input("Select an option (1-6): ");
+        let choice = read_input("Select an option (1-14): ");
         
         match choice.as_str() {
             "1" => {
@@ -168,9 +699,13 @@ fn interactive_admin_operations(conn: &Connection) {
                 let fullname = read_input("Enter voter full name: ");
                 let dob = read_input("Enter date of birth: ");
                 let pin = read_input("Enter PIN: ");
-                
-                match admin.register_voter(&fullname, &dob, &pin) {
-                    Ok(id) => println!("✅ Voter '{}' registered with ID {}", fullname, id),
+                let district = read_input("Enter district (blank if none): ");
+                let station = if district.is_empty() { String::new() } else { read_input("Enter polling station (blank if none): ") };
+                let district = Some(district.as_str()).filter(|d| !d.is_empty());
+                let station = Some(station.as_str()).filter(|s| !s.is_empty());
+
+                match admin.register_voter(&fullname, &dob, &pin, district, station) {
+                    Ok(v) => println!("✅ Voter '{}' registered with voter number {} (ID {})", v.fullname, v.voter_number, v.id),
                     Err(e) => println!("❌ Error registering voter: {}", e),
                 }
             }
@@ -186,33 +721,82 @@ fn interactive_admin_operations(conn: &Connection) {
                     _ => println!("❌ Invalid election ID"),
                 }
             }
-            "5" => {
-                let username = read_input("Enter admin username: ");
-                let password = read_input("Enter admin password: ");
-                
-                let result: Result<(String, String), _> = conn.query_row(
-                    "SELECT username, password_hash FROM admins WHERE username=?1",
-                    params![username],
-                    |row| Ok((row.get(0)?, row.get(1)?)),
-                );
-
-                match result {
-                    Ok((stored_user, stored_hash)) => {
-                        if verify_password(&stored_hash, &password) {
-                            println!("✅ Admin '{}' successfully logged in!", stored_user);
-                        } else {
-                            println!("❌ Incorrect password.");
+            "5" => interactive_transition(&admin, "is now OPEN", AdminService::open_election),
+            "6" => interactive_transition(&admin, "has been CLOSED", AdminService::close_election),
+            "7" => interactive_transition(&admin, "has been SUSPENDED", AdminService::suspend_election),
+            "8" => interactive_transition(&admin, "has been CANCELLED", AdminService::cancel_election),
+            "9" => interactive_transition(&admin, "has been CERTIFIED", AdminService::certify_election),
+            "10" => {
+                let election_id = read_input("Enter election ID: ");
+                let position_idx = read_input("Enter position index: ");
+                let method = read_input("Enter ballot method (plurality, ranked, stv, approval, score): ");
+                let mut rules = BallotRules::default();
+                match method.parse::<BallotMethod>() {
+                    Ok(m) if m.is_multi_select() => {
+                        if m == BallotMethod::Stv {
+                            rules.seats = read_input("Enter number of seats: ").parse().unwrap_or(0);
                         }
+                        rules.min_selections = read_input("Minimum candidates to mark (0 for none): ").parse().unwrap_or(0);
+                        rules.max_selections = read_input("Maximum candidates to mark (0 for no limit): ").parse().unwrap_or(0);
+                        if m == BallotMethod::Score {
+                            rules.max_score = read_input("Maximum score per candidate: ").parse().unwrap_or(0);
+                        }
+                    }
+                    Ok(BallotMethod::Plurality) => {
+                        rules.allow_write_in = read_input("Allow write-ins? (y/N): ").eq_ignore_ascii_case("y");
                     }
-                    Err(_) => println!("⚠️ No such admin found."),
+                    _ => {}
+                }
+
+                match (election_id.parse::<i64>(), position_idx.parse::<i32>(), method.parse::<BallotMethod>()) {
+                    (Ok(eid), Ok(pidx), Ok(m)) => match admin.set_ballot_method(eid, pidx, &BallotRules { method: m, ..rules }) {
+                        Ok(_) => println!("✅ Position {} of election #{} now uses {}", pidx, eid, m),
+                        Err(e) => println!("❌ Error setting ballot method: {}", e),
+                    },
+                    (_, _, Err(e)) => println!("❌ {}", e),
+                    _ => println!("❌ Invalid election ID or position index"),
+                }
+            }
+            "11" => {
+                let election_id = read_input("Enter election ID: ");
+                let text = read_input("Enter question text: ");
+                let options_input = read_input("Enter options, comma-separated (blank for Yes,No): ");
+                let threshold = read_input("Pass threshold (majority, two-thirds) [majority]: ");
+                let quorum = read_input("Quorum as % of registered voters [0]: ");
+
+                let options: Vec<&str> = options_input.split(',').map(|o| o.trim()).filter(|o| !o.is_empty()).collect();
+                let threshold = if threshold.is_empty() { Ok(PassThreshold::SimpleMajority) } else { threshold.parse::<PassThreshold>() };
+                let quorum = if quorum.is_empty() { Ok(0) } else { quorum.parse::<u32>() };
+
+                match (election_id.parse::<i64>(), threshold, quorum) {
+                    (Ok(eid), Ok(t), Ok(q)) => match admin.add_question(eid, &text, &options, t, q) {
+                        Ok(qid) => println!("✅ Question #{} added to election #{}", qid, eid),
+                        Err(e) => println!("❌ Error adding question: {}", e),
+                    },
+                    (_, Err(e), _) => println!("❌ {}", e),
+                    _ => println!("❌ Invalid election ID or quorum"),
                 }
             }
-            "6" => break,
+            "12" => {
+                let election_id = read_input("Enter election ID: ");
+                match election_id.parse::<i64>() {
+                    Ok(eid) => interactive_write_in_review(&admin, eid),
+                    _ => println!("❌ Invalid election ID"),
+                }
+            }
+            "13" => {
+                match auth::logout(conn, &token) {
+                    Ok(_) => println!("👋 Logged out."),
+                    Err(e) => println!("❌ Error logging out: {}", e),
+                }
+                break;
+            }
+            "14" => break,
             "" => {
-                println!("⚠️  Please enter a valid option (1-6).");
+                println!("⚠️  Please enter a valid option (1-14).");
                 continue;
             }
-            _ => println!("❌ Invalid option. Please select 1-6."),
+            _ => println!("❌ Invalid option. Please select 1-14."),
         }
         
         read_input("\nPress Enter to continue...");
@@ -222,7 +806,7 @@ fn interactive_admin_operations(conn: &Connection) {
 fn interactive_menu(conn: &Connection) {
     loop {
         show_main_menu();
-        let choice = read_input("Select an option (1-5): ");
+        let choice = read_input("Select an option (1-6): ");
         
         match choice.as_str() {
             "1" => {
@@ -236,15 +820,27 @@ fn interactive_menu(conn: &Connection) {
                     .unwrap_or(false);
 
                 if exists {
-                    println!("⚠️  An admin already exists. Only one admin can be initialized this way.");
+                    println!("⚠️  An admin already exists. Only one admin can be initialized this way; use `admin user add` for more.");
                 } else {
-                    let hash = hash_password(&admin_pass);
-                    match conn.execute(
-                        "INSERT INTO admins (username, password_hash, created_at) VALUES (?1, ?2, ?3)",
-                        params![admin_user, hash, Utc::now().to_rfc3339()],
-                    ) {
-                        Ok(_) => println!("✅ Admin '{}' created and stored securely!", admin_user),
+                    match hash_new_password(conn, None, &admin_user, &admin_pass) {
                         Err(e) => println!("❌ Error creating admin: {}", e),
+                        Ok(hash) => match conn.execute(
+                            "INSERT INTO admins (username, password_hash, created_at) VALUES (?1, ?2, ?3)",
+                            params![admin_user, hash, Utc::now().to_rfc3339()],
+                        ) {
+                            Ok(_) => {
+                                let admin_id = conn.last_insert_rowid();
+                                if let Err(e) = auth::grant_role(conn, admin_id, Role::SuperAdmin, None) {
+                                    println!("❌ Error granting admin role: {}", e);
+                                }
+                                if let Err(e) = auth::remember_password(conn, &auth::history_owner("admin", admin_id), &hash) {
+                                    println!("❌ Error recording password history: {}", e);
+                                }
+                                record_audit(conn, &admin_user, "create_admin", "initial admin");
+                                println!("✅ Admin '{}' created and stored securely!", admin_user)
+                            }
+                            Err(e) => println!("❌ Error creating admin: {}", e),
+                        }
                     }
                 }
             }
@@ -252,33 +848,173 @@ fn interactive_menu(conn: &Connection) {
                 interactive_admin_operations(conn);
             }
             "3" => {
-                if let Some((voter_id, voter_name)) = voter_login(conn) {
-                    voter_portal(conn, voter_id, &voter_name);
+                if let Some((voter_id, voter_name, voting_token)) = voter_login(conn) {
+                    voter_portal(conn, voter_id, &voter_name, voting_token.as_ref());
                 }
             }
             "4" => {
                 print_elections(conn);
             }
             "5" => {
+                interactive_district_operations(conn);
+            }
+            "6" => {
                 println!("👋 Goodbye!");
                 break;
             }
             "" => {
-                println!("⚠️  Please enter a valid option (1-5).");
+                println!("⚠️  Please enter a valid option (1-6).");
                 continue;
             }
-            _ => println!("❌ Invalid option. Please select 1-5."),
+            _ => println!("❌ Invalid option. Please select 1-6."),
         }
         
-        if choice != "5" {
+        if choice != "6" {
             read_input("\nPress Enter to continue...");
         }
     }
 }
 
+fn show_district_menu(place: &str, election_id: i64) {
+    println!("\n🏫 === District Official Console: {} (election #{}) ===", place, election_id);
+    println!("1. Look Up Voter");
+    println!("2. Check In Voter");
+    println!("3. Voters Who Have Voted");
+    println!("4. Check-ins vs Ballots Cast");
+    println!("5. Logout");
+    println!("=================================");
+}
+
+/// Electronic poll book for a district official: look voters up on the
+/// district roll, check them in, and follow turnout at their station.
+fn interactive_district_operations(conn: &Connection) {
+    let Some(token) = interactive_login(conn, "District Official Login") else { return };
+
+    let place = match auth::validate_session(conn, &token).map_err(|e| e.to_string()).and_then(|session| {
+        AdminService::new(conn, &session).poll_book_scope().map_err(|e| e.to_string())
+    }) {
+        Ok((district_id, station_id)) => {
+            let district = district::district_name(conn, district_id).unwrap_or_else(|_| format!("district #{}", district_id));
+            match station_id.and_then(|s| district::station_name(conn, s).ok()) {
+                Some(station) => format!("{} / {}", district, station),
+                None => district,
+            }
+        }
+        Err(e) => {
+            println!("❌ {}", e);
+            let _ = auth::logout(conn, &token);
+            return;
+        }
+    };
+
+    print_elections(conn);
+    let election_id = match read_input("Enter election ID for this session: ").parse::<i64>() {
+        Ok(eid) if election::election_status(conn, eid).is_ok() => eid,
+        _ => {
+            println!("❌ Invalid election ID");
+            let _ = auth::logout(conn, &token);
+            return;
+        }
+    };
+
+    loop {
+        // Re-checked every time so expiry and revocation take effect mid-shift.
+        let session = match auth::validate_session(conn, &token) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("❌ {}", e);
+                break;
+            }
+        };
+        let official = AdminService::new(conn, &session);
+
+        show_district_menu(&place, election_id);
+        let choice = read_input("Select an option (1-5): ");
+
+        match choice.as_str() {
+            "1" => {
+                let query = read_input("Voter number or name: ");
+                match official.find_on_roll(election_id, &query) {
+                    Ok(entries) if entries.is_empty() => println!("No voter on this district's roll matches '{}'.", query),
+                    Ok(entries) => {
+                        for e in entries {
+                            let status = if e.voted {
+                                "🗳️  voted".to_string()
+                            } else if let Some(at) = &e.checked_in_at {
+                                format!("✅ checked in {}", at)
+                            } else {
+                                "not checked in".to_string()
+                            };
+                            let station = e.station.map(|s| format!(", {}", s)).unwrap_or_default();
+                            println!(" - {} {} (born {}{}): {}", e.voter.voter_number, e.voter.fullname, e.voter.dob, station, status);
+                        }
+                    }
+                    Err(e) => println!("❌ {}", e),
+                }
+            }
+            "2" => {
+                let voter_number = read_input("Voter number: ");
+                match official.check_in(election_id, &voter_number) {
+                    Ok(voter) => {
+                        println!("✅ {} ({}) checked in.", voter.fullname, voter.voter_number);
+                        if read_input("Issue a voting token now? (y/N): ").eq_ignore_ascii_case("y") {
+                            match official.issue_voting_token(&voter.voter_number, election_id) {
+                                Ok((_, code, expires_at)) => println!("🎫 Voting token: {}  (valid once, until {})", code, expires_at),
+                                Err(e) => println!("❌ Error issuing voting token: {}", e),
+                            }
+                        }
+                    }
+                    Err(e) => println!("❌ {}", e),
+                }
+            }
+            "3" => match official.voted_on_roll(election_id) {
+                Ok(voters) if voters.is_empty() => println!("Nobody on this district's roll has voted yet."),
+                Ok(voters) => {
+                    println!("\n🗳️  Voted ({}):", voters.len());
+                    for v in voters {
+                        println!(" - {} {}", v.voter_number, v.fullname);
+                    }
+                }
+                Err(e) => println!("❌ {}", e),
+            },
+            "4" => match official.station_counts(election_id) {
+                Ok((checked_in, ballots)) => {
+                    println!("\n📊 {}: {} checked in, {} ballot(s) cast", place, checked_in, ballots);
+                    if ballots > checked_in {
+                        println!("⚠️  More ballots than check-ins; voters may be voting without checking in here.");
+                    } else if checked_in > ballots {
+                        println!("   {} checked in but not yet voted.", checked_in - ballots);
+                    }
+                }
+                Err(e) => println!("❌ {}", e),
+            },
+            "5" => {
+                if let Err(e) = auth::logout(conn, &token) {
+                    println!("❌ Error logging out: {}", e);
+                }
+                println!("👋 Logged out.");
+                break;
+            }
+            "" => {
+                println!("⚠️  Please enter a valid option (1-5).");
+                continue;
+            }
+            _ => println!("❌ Invalid option. Please select 1-5."),
+        }
+        read_input("\nPress Enter to continue...");
+    }
+}
+
+/// Audit entries for events outside AdminService; a failed write is
+/// reported but does not undo what already happened.
+fn record_audit(conn: &Connection, actor: &str, action: &str, detail: &str) {
+    if let Err(e) = audit::record(conn, actor, action, detail) {
+        println!("⚠️  Could not write audit log: {}", e);
+    }
+}
+
 fn print_elections(conn: &Connection) {
-    let admin = AdminService::new(conn);
-    match admin.list_elections() {
+    match election::list_elections(conn) {
         Ok(elections) => {
             println!("\n📋 Elections:");
             if elections.is_empty() {
@@ -293,61 +1029,60 @@ fn print_elections(conn: &Connection) {
     }
 }
 
+fn print_write_in_queue(queue: &[models::WriteIn]) {
+    println!("\n✍️  Write-ins awaiting review:");
+    if queue.is_empty() {
+        println!("No write-ins awaiting review.");
+    }
+    for w in queue {
+        println!(" - #{} position {}: \"{}\"", w.id, w.position_idx, w.name);
+    }
+}
+
 // voter-related functions moved to voter.rs
 
+/// Bring the schema up to date (see db.rs). Runs before every command.
 fn migrate(conn: &Connection) {
-    conn.execute_batch(
-        r#"
-        PRAGMA foreign_keys = ON;
-
-        CREATE TABLE IF NOT EXISTS admins (
-            id INTEGER PRIMARY KEY,
-            username TEXT NOT NULL UNIQUE,
-            password_hash TEXT NOT NULL,
-            created_at TEXT NOT NULL
-        );
-
-        CREATE TABLE IF NOT EXISTS elections (
-            id INTEGER PRIMARY KEY,
-            name TEXT NOT NULL,
-            status TEXT NOT NULL DEFAULT 'Draft',
-            created_at TEXT NOT NULL
-        );
-
-        CREATE TABLE IF NOT EXISTS positions (
-            id INTEGER PRIMARY KEY,
-            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
-            idx INTEGER NOT NULL,
-            title TEXT NOT NULL
-        );
-
-        CREATE TABLE IF NOT EXISTS candidates (
-            id INTEGER PRIMARY KEY,
-            election_id INTEGER NOT NULL REFERENCES elections(id) ON DELETE CASCADE,
-            position_idx INTEGER NOT NULL,
-            name TEXT NOT NULL,
-            party TEXT NOT NULL
-        );
-
-        CREATE TABLE IF NOT EXISTS voters (
-            id INTEGER PRIMARY KEY,
-            fullname TEXT NOT NULL,
-            dob TEXT NOT NULL,
-            pinhash TEXT NOT NULL
-        );
-
-        CREATE TABLE IF NOT EXISTS votes (
-            id INTEGER PRIMARY KEY,
-            election_id INTEGER NOT NULL,
-            voter_id INTEGER NOT NULL,
-            position_idx INTEGER NOT NULL,
-            candidate_id INTEGER NOT NULL,
-            cast_at TEXT NOT NULL
-        );
-    "#,
-    )
-    .unwrap();
-    println!("Database migration complete ✅");
+    match db::migrate(conn) {
+        Ok(applied) if applied.is_empty() => {}
+        Ok(applied) => {
+            for (m, notes) in &applied {
+                println!("🧱 Applied migration {}: {}", m.version, m.description);
+                for note in notes {
+                    println!("   - {note}");
+                }
+            }
+            println!("Database migration complete ✅ (schema version {})", db::latest_version());
+        }
+        Err(e) => {
+            println!("❌ Database migration failed: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_migration_status(conn: &Connection) {
+    let applied = match db::applied(conn) {
+        Ok(applied) => applied,
+        Err(e) => {
+            println!("❌ Error reading schema version: {e}");
+            return;
+        }
+    };
+    let current = applied.iter().map(|(v, _)| *v).max().unwrap_or(0);
+    println!("\n🧱 Schema version {current} of {}:", db::latest_version());
+    if db::is_unversioned(conn).unwrap_or(false) {
+        println!("   This database predates versioned migrations; the pending ones only add what it is missing.");
+    }
+    for m in db::MIGRATIONS {
+        match applied.iter().find(|(v, _)| *v == m.version) {
+            Some((_, at)) => println!(" ✅ {:>3}  {} (applied {at})", m.version, m.description),
+            None => println!(" ⏳ {:>3}  {} (pending)", m.version, m.description),
+        }
+    }
+    for (v, at) in applied.iter().filter(|(v, _)| *v > db::latest_version()) {
+        println!(" ⚠️  {v:>3}  unknown to this version of rusttrust (applied {at})");
+    }
 }
 
 // --------------------------- MAIN ----------------------------------
@@ -355,6 +1090,11 @@ fn migrate(conn: &Connection) {
 fn main() {
     let cli = Cli::parse();
     let conn = connect(&cli.db);
+    // Status must see the schema as it is, before anything is applied.
+    if let Some(Commands::Db(DbCmd { sub: DbSub::Migrate { status: true } })) = cli.cmd {
+        print_migration_status(&conn);
+        return;
+    }
     migrate(&conn);
 
     match cli.cmd {
@@ -367,67 +1107,430 @@ fn main() {
                 .unwrap_or(false);
 
             if exists {
-                println!("⚠️  An admin already exists. Only one admin can be initialized this way.");
+                println!("⚠️  An admin already exists. Only one admin can be initialized this way; use `admin user add` for more.");
                 return;
             }
 
-            let hash = hash_password(&admin_pass);
+            let hash = match hash_new_password(&conn, None, &admin_user, &admin_pass) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    println!("❌ Error creating admin: {e}");
+                    return;
+                }
+            };
             conn.execute(
                 "INSERT INTO admins (username, password_hash, created_at) VALUES (?1, ?2, ?3)",
                 params![admin_user, hash, Utc::now().to_rfc3339()],
             )
             .expect("Failed to insert admin");
+            let admin_id = conn.last_insert_rowid();
+            auth::grant_role(&conn, admin_id, Role::SuperAdmin, None).expect("Failed to grant admin role");
+            auth::remember_password(&conn, &auth::history_owner("admin", admin_id), &hash)
+                .expect("Failed to record password history");
+            record_audit(&conn, &admin_user, "create_admin", "initial admin");
 
             println!("✅ Admin '{admin_user}' created and stored securely!");
         }
 
         Some(Commands::Admin(ac)) => {
-            let admin = AdminService::new(&conn);
+            let session_path = auth::session_file(&cli.db);
+            let sub = match ac.sub {
+                AdminSub::Login { username, password, otp, ttl_minutes } => {
+                    match auth::login_admin(&conn, &username, &password, otp.as_deref(), ttl_minutes) {
+                        Ok((token, session)) => match auth::save_session_token(&session_path, &token) {
+                            Ok(_) => println!("✅ Admin '{}' successfully logged in! Session expires {}", session.username, session.expires_at),
+                            Err(e) => println!("❌ Logged in but could not save session to {}: {e}", session_path.display()),
+                        },
+                        Err(e @ error::AppError::TwoFactorRequired) => println!("❌ {e}; pass it with --otp"),
+                        Err(e) => println!("❌ {e}"),
+                    }
+                    return;
+                }
+                AdminSub::Logout => {
+                    if let Some(token) = auth::load_session_token(&session_path) {
+                        if let Err(e) = auth::logout(&conn, &token) {
+                            println!("❌ Error logging out: {e}");
+                            return;
+                        }
+                    }
+                    let _ = std::fs::remove_file(&session_path);
+                    println!("👋 Logged out.");
+                    return;
+                }
+                sub => sub,
+            };
+
+            let session = match auth::current_session(&conn, &session_path) {
+                Ok(s) => s,
+                Err(e) => {
+                    println!("❌ {e}");
+                    std::process::exit(1);
+                }
+            };
+            let admin = AdminService::new(&conn, &session);
+
+            match sub {
+                AdminSub::Login { .. } | AdminSub::Logout => unreachable!("handled before the session check"),
+
+                AdminSub::Whoami => {
+                    println!("👤 Logged in as '{}' (session #{}, expires {})", session.username, session.id, session.expires_at);
+                }
+
+                AdminSub::RevokeSessions { username } => match admin.revoke_sessions(&username) {
+                    Ok(n) => println!("✅ Revoked {n} session(s) of '{username}'"),
+                    Err(e) => println!("❌ Error revoking sessions: {e}"),
+                },
+
+                AdminSub::GrantRole { username, role } => match admin.grant_role(&username, role) {
+                    Ok(true) => println!("✅ '{username}' is now {role}"),
+                    Ok(false) => println!("⚠️  '{username}' already has {role}"),
+                    Err(e) => println!("❌ Error granting role: {e}"),
+                },
+
+                AdminSub::RevokeRole { username, role } => match admin.revoke_role(&username, role) {
+                    Ok(true) => println!("✅ '{username}' is no longer {role}"),
+                    Ok(false) => println!("⚠️  '{username}' did not have {role}"),
+                    Err(e) => println!("❌ Error revoking role: {e}"),
+                },
+
+                AdminSub::User(uc) => match uc.sub {
+                    UserSub::Add { username, password, roles } => match admin.add_admin(&username, &password, &roles) {
+                        Ok(id) => println!("✅ Admin '{username}' created with ID {id}"),
+                        Err(e) => println!("❌ Error creating admin: {e}"),
+                    },
+                    UserSub::List => match admin.list_admins() {
+                        Ok(accounts) => {
+                            println!("\n👥 Admin accounts:");
+                            for a in accounts {
+                                let roles: Vec<&str> = a.roles.iter().map(|r| r.as_str()).collect();
+                                let created_by = a.created_by.as_deref().unwrap_or("init");
+                                let state = if a.disabled { " [DISABLED]" } else { "" };
+                                println!(" - #{} {}{state}: {} (created {} by {created_by})",
+                                    a.id, a.username, roles.join(", "), a.created_at);
+                            }
+                        }
+                        Err(e) => println!("❌ Error listing admins: {e}"),
+                    },
+                    UserSub::Disable { username } => match admin.disable_admin(&username) {
+                        Ok(_) => println!("✅ Admin '{username}' disabled"),
+                        Err(e) => println!("❌ Error disabling admin: {e}"),
+                    },
+                    UserSub::Enable { username } => match admin.enable_admin(&username) {
+                        Ok(_) => println!("✅ Admin '{username}' enabled"),
+                        Err(e) => println!("❌ Error enabling admin: {e}"),
+                    },
+                    UserSub::ResetPassword { username, password } => match admin.reset_admin_password(&username, &password) {
+                        Ok(false) => println!("✅ Password for '{username}' reset; their sessions were ended"),
+                        Ok(true) => println!("✅ Password for '{username}' reset; their sessions were ended and two-factor must be set up again"),
+                        Err(e) => println!("❌ Error resetting password: {e}"),
+                    },
+                },
+
+                AdminSub::Lockouts => match admin.lockouts() {
+                    Ok(list) => {
+                        println!("\n⛔ Failed logins:");
+                        if list.is_empty() {
+                            println!("No recent failed logins.");
+                        }
+                        for l in list {
+                            match l.locked_until {
+                                Some(until) => println!(" - {}: {} failures, LOCKED until {until}", l.key, l.failures),
+                                None => println!(" - {}: {} failures, last at {}", l.key, l.failures, l.last_failure_at),
+                            }
+                        }
+                    }
+                    Err(e) => println!("❌ Error listing lockouts: {e}"),
+                },
+
+                AdminSub::ClearLockout { key, all } => {
+                    if key.is_none() && !all {
+                        println!("❌ Give a key to clear, or --all");
+                    } else {
+                        match admin.clear_lockout(key.as_deref()) {
+                            Ok(n) => println!("✅ Cleared {n} lockout record(s)"),
+                            Err(e) => println!("❌ Error clearing lockout: {e}"),
+                        }
+                    }
+                }
+
+                AdminSub::Config(cc) => match cc.sub {
+                    ConfigSub::Show => match admin.settings() {
+                        Ok(settings) => {
+                            println!("\n⚙️  Settings:");
+                            for (s, value) in settings {
+                                println!(" - {} = {value} (default {}, {}..{})", s.key, s.default, s.min, s.max);
+                                println!("     {}", s.description);
+                            }
+                        }
+                        Err(e) => println!("❌ Error reading settings: {e}"),
+                    },
+                    ConfigSub::Set { key, value } => match admin.set_setting(&key, value) {
+                        Ok(_) => println!("✅ {key} = {value}"),
+                        Err(e) => println!("❌ Error changing setting: {e}"),
+                    },
+                },
+
+                AdminSub::ResetVoterPin { voter_number } => match admin.reset_voter_pin(&voter_number) {
+                    Ok((voter, pin)) => {
+                        println!("🔑 Temporary PIN for {} ({}): {pin}", voter.fullname, voter.voter_number);
+                        println!("   They will be asked to choose a new PIN when they next log in.");
+                    }
+                    Err(e) => println!("❌ Error resetting PIN: {e}"),
+                },
+
+                AdminSub::IssueToken { voter_number, election_id } => match admin.issue_voting_token(&voter_number, election_id) {
+                    Ok((voter, code, expires_at)) => {
+                        println!("🎫 Voting token for {} ({}), election #{election_id}:", voter.fullname, voter.voter_number);
+                        println!("   {code}");
+                        println!("   Valid once, until {expires_at}. Enter it instead of the PIN.");
+                    }
+                    Err(e) => println!("❌ Error issuing voting token: {e}"),
+                },
+
+                AdminSub::District(dc) => match dc.sub {
+                    DistrictSub::Add { name, stations } => match admin.add_district(&name, &stations) {
+                        Ok(id) => println!("✅ District '{name}' created with ID {id} and {} polling station(s)", stations.len()),
+                        Err(e) => println!("❌ Error creating district: {e}"),
+                    },
+                    DistrictSub::AddStation { district, name } => match admin.add_station(&district, &name) {
+                        Ok(id) => println!("✅ Polling station '{name}' added to '{district}' with ID {id}"),
+                        Err(e) => println!("❌ Error adding polling station: {e}"),
+                    },
+                    DistrictSub::List => match admin.list_districts() {
+                        Ok(districts) if districts.is_empty() => println!("No districts yet."),
+                        Ok(districts) => {
+                            println!("\n🗺️  Districts:");
+                            for d in districts {
+                                println!(" - {}: {} ({} voter(s))", d.id, d.name, d.voters);
+                                for s in d.stations {
+                                    println!("     🏫 {}: {}", s.id, s.name);
+                                }
+                            }
+                        }
+                        Err(e) => println!("❌ Error listing districts: {e}"),
+                    },
+                    DistrictSub::Assign { voter_number, district, station } => {
+                        match admin.assign_voter(&voter_number, &district, station.as_deref()) {
+                            Ok(v) => println!("✅ Voter {} ({}) assigned to '{district}'", v.voter_number, v.fullname),
+                            Err(e) => println!("❌ Error assigning voter: {e}"),
+                        }
+                    }
+                    DistrictSub::AssignOfficial { username, district, station } => {
+                        match admin.assign_official(&username, &district, station.as_deref()) {
+                            Ok(_) => println!("✅ '{username}' keeps the poll book for '{district}'"),
+                            Err(e) => println!("❌ Error assigning official: {e}"),
+                        }
+                    }
+                    DistrictSub::ScopePosition { election_id, position_idx, districts } => {
+                        match admin.scope_position(election_id, position_idx, &districts) {
+                            Ok(_) if districts.is_empty() => println!("✅ Position {position_idx} is contested in every district"),
+                            Ok(_) => println!("✅ Position {position_idx} is contested only in: {}", districts.join(", ")),
+                            Err(e) => println!("❌ Error scoping position: {e}"),
+                        }
+                    }
+                },
+
+                AdminSub::Station(sc) => match sc.sub {
+                    StationSub::Init { district, station, passphrase } => match admin.init_station(&district, &station, &passphrase) {
+                        Ok(key) => {
+                            println!("✅ This database is now polling station '{station}' in '{district}'.");
+                            println!("🔑 Public key, to trust on the central instance:");
+                            println!("   {key}");
+                        }
+                        Err(e) => println!("❌ Error setting up station: {e}"),
+                    },
+                    StationSub::Show => match admin.station_identity() {
+                        Ok(Some((district, station, key))) => {
+                            println!("🏫 Polling station '{station}' in '{district}'");
+                            println!("🔑 {key}");
+                        }
+                        Ok(None) => println!("This database is not an offline polling station."),
+                        Err(e) => println!("❌ Error reading station identity: {e}"),
+                    },
+                    StationSub::Trust { district, station, public_key } => {
+                        match admin.trust_station(&district, &station, &public_key) {
+                            Ok(_) => println!("✅ Results signed by this key are accepted from '{station}' in '{district}'"),
+                            Err(e) => println!("❌ Error trusting station key: {e}"),
+                        }
+                    }
+                    StationSub::Results { election_id } => {
+                        if let Err(e) = admin.view_station_results(election_id) {
+                            println!("❌ Error viewing station results: {e}");
+                        }
+                    }
+                },
+
+                AdminSub::ExportStationResults { election_id, passphrase, output } => match admin.export_station_results(election_id, &passphrase) {
+                    Ok((report, package)) => {
+                        let path = output.unwrap_or_else(|| {
+                            PathBuf::from(format!("station-results-{election_id}-{}.json", report.package_id))
+                        });
+                        match std::fs::write(&path, package) {
+                            Ok(_) => {
+                                println!("✅ Signed results for election #{election_id} written to {}", path.display());
+                                println!("   {} ballot(s), {} voter(s), box hash {}",
+                                    report.ballot_count, report.participation.len(), report.ballot_box_hash);
+                            }
+                            Err(e) => println!("❌ Could not write {}: {e}", path.display()),
+                        }
+                    }
+                    Err(e) => println!("❌ Error exporting station results: {e}"),
+                },
+
+                AdminSub::ImportStationResults { file } => match std::fs::read_to_string(&file) {
+                    Ok(package) => match admin.import_station_results(&package) {
+                        Ok(report) => {
+                            println!("✅ Imported results from '{}' in '{}' for election #{}",
+                                report.station, report.district, report.election_id);
+                            println!("   {} ballot(s), box hash {}", report.ballot_count, report.ballot_box_hash);
+                        }
+                        Err(e) => {
+                            println!("❌ {e}");
+                            std::process::exit(1);
+                        }
+                    },
+                    Err(e) => println!("❌ Could not read {}: {e}", file.display()),
+                },
+
+                AdminSub::Totp(tc) => match tc.sub {
+                    TotpSub::Enroll { password } => match admin.enroll_totp(&password) {
+                        Ok((uri, codes)) => {
+                            println!("🔑 Add this account to your authenticator app (scan as a QR code or paste):");
+                            println!("   {uri}");
+                            println!("\n📝 Recovery codes, each usable once if the device is lost. Store them offline:");
+                            for code in codes {
+                                println!("   {code}");
+                            }
+                            println!("\nFinish with: admin totp confirm <code> -p <password>");
+                        }
+                        Err(e) => println!("❌ Error enrolling two-factor: {e}"),
+                    },
+                    TotpSub::Confirm { code, password } => match admin.confirm_totp(&password, &code) {
+                        Ok(_) => println!("✅ Two-factor enabled; logins now need a code"),
+                        Err(e) => println!("❌ Error confirming two-factor: {e}"),
+                    },
+                    TotpSub::Disable { user } => match admin.disable_totp(user.as_deref()) {
+                        Ok(_) => println!("✅ Two-factor removed"),
+                        Err(e) => println!("❌ Error removing two-factor: {e}"),
+                    },
+                },
+
+                AdminSub::Roles => match admin.list_roles() {
+                    Ok(admins) => {
+                        println!("\n🔑 Admin roles:");
+                        for (username, roles) in admins {
+                            let roles: Vec<&str> = roles.iter().map(|r| r.as_str()).collect();
+                            let roles = if roles.is_empty() { "(none)".to_string() } else { roles.join(", ") };
+                            println!(" - {username}: {roles}");
+                        }
+                    }
+                    Err(e) => println!("❌ Error listing roles: {e}"),
+                },
 
-            match ac.sub {
                 AdminSub::CreateElection { name, positions } => {
                     let pos: Vec<&str> = positions.split(',').map(|p| p.trim()).collect();
-                    let eid = admin.create_election(&name, &pos).unwrap();
-                    println!("✅ Election '{name}' created with ID {eid}");
+                    match admin.create_election(&name, &pos) {
+                        Ok(eid) => println!("✅ Election '{name}' created with ID {eid}"),
+                        Err(e) => println!("❌ Error creating election: {e}"),
+                    }
+                }
+
+                AdminSub::SetBallotMethod {
+                    election_id, position_idx, method, seats, min_selections, max_selections, max_score, allow_write_in,
+                } => {
+                    let rules = BallotRules { method, seats, min_selections, max_selections, max_score, allow_write_in };
+                    match admin.set_ballot_method(election_id, position_idx, &rules) {
+                        Ok(_) => println!("✅ Position {position_idx} of election #{election_id} now uses {method}"),
+                        Err(e) => println!("❌ Error setting ballot method: {e}"),
+                    }
+                }
+
+                AdminSub::AddQuestion { election_id, text, options, threshold, quorum } => {
+                    let opts: Vec<&str> = options.split(',').map(|o| o.trim()).filter(|o| !o.is_empty()).collect();
+                    match admin.add_question(election_id, &text, &opts, threshold, quorum) {
+                        Ok(qid) => println!("✅ Question #{qid} added to election #{election_id}"),
+                        Err(e) => println!("❌ Error adding question: {e}"),
+                    }
+                }
+
+                AdminSub::WriteIns { election_id } => match admin.write_in_queue(election_id) {
+                    Ok(queue) => print_write_in_queue(&queue),
+                    Err(e) => println!("❌ Error listing write-ins: {e}"),
+                },
+
+                AdminSub::MergeWriteIn { write_in_id, candidate_id } => {
+                    match admin.merge_write_in(write_in_id, candidate_id) {
+                        Ok(n) => println!("✅ {n} write-in(s) counted for candidate #{candidate_id}"),
+                        Err(e) => println!("❌ Error merging write-in: {e}"),
+                    }
                 }
 
+                AdminSub::AcceptWriteIn { write_in_id, party } => match admin.accept_write_in(write_in_id, &party) {
+                    Ok((cid, n)) => println!("✅ Candidate #{cid} created; {n} write-in(s) counted"),
+                    Err(e) => println!("❌ Error accepting write-in: {e}"),
+                },
+
+                AdminSub::RejectWriteIn { write_in_id } => match admin.reject_write_in(write_in_id) {
+                    Ok(n) => println!("✅ {n} write-in(s) rejected"),
+                    Err(e) => println!("❌ Error rejecting write-in: {e}"),
+                },
+
                 AdminSub::AddCandidate {
                     election_id,
                     position_idx,
                     name,
                     party,
                 } => {
-                    admin.add_candidate(election_id, position_idx, &name, &party).unwrap();
-                    println!("✅ Candidate '{name}' added to election #{election_id}");
+                    match admin.add_candidate(election_id, position_idx, &name, &party) {
+                        Ok(_) => println!("✅ Candidate '{name}' added to election #{election_id}"),
+                        Err(e) => println!("❌ Error adding candidate: {e}"),
+                    }
                 }
 
-                AdminSub::RegisterVoter { fullname, dob, pin } => {
-                    let id = admin.register_voter(&fullname, &dob, &pin).unwrap();
-                    println!("✅ Voter '{fullname}' registered with ID {id}");
+                AdminSub::RegisterVoter { fullname, dob, pin, district, station } => {
+                    match admin.register_voter(&fullname, &dob, &pin, district.as_deref(), station.as_deref()) {
+                        Ok(v) => println!("✅ Voter '{}' registered with voter number {} (ID {})", v.fullname, v.voter_number, v.id),
+                        Err(e) => println!("❌ Error registering voter: {e}"),
+                    }
                 }
 
                 AdminSub::ViewResults { election_id } => {
-                    admin.view_results(election_id).unwrap();
+                    if let Err(e) = admin.view_results(election_id) {
+                        println!("❌ Error viewing results: {e}");
+                    }
                 }
 
-                AdminSub::Login { username, password } => {
-                    let result: Result<(String, String), _> = conn.query_row(
-                        "SELECT username, password_hash FROM admins WHERE username=?1",
-                        params![username],
-                        |row| Ok((row.get(0)?, row.get(1)?)),
-                    );
+                AdminSub::DistrictResults { election_id } => match admin.view_district_results(election_id) {
+                    Ok(true) => {}
+                    Ok(false) => std::process::exit(1),
+                    Err(e) => println!("❌ Error viewing district results: {e}"),
+                },
+
+                AdminSub::OpenElection { election_id } => match admin.open_election(election_id) {
+                    Ok(_) => println!("✅ Election #{election_id} is now OPEN."),
+                    Err(e) => println!("❌ Error: {e}"),
+                },
+
+                AdminSub::CloseElection { election_id } => match admin.close_election(election_id) {
+                    Ok(_) => println!("✅ Election #{election_id} has been CLOSED."),
+                    Err(e) => println!("❌ Error: {e}"),
+                },
+
+                AdminSub::SuspendElection { election_id } => match admin.suspend_election(election_id) {
+                    Ok(_) => println!("✅ Election #{election_id} has been SUSPENDED."),
+                    Err(e) => println!("❌ Error: {e}"),
+                },
+
+                AdminSub::CancelElection { election_id } => match admin.cancel_election(election_id) {
+                    Ok(_) => println!("✅ Election #{election_id} has been CANCELLED."),
+                    Err(e) => println!("❌ Error: {e}"),
+                },
+
+                AdminSub::Certify { election_id } => match admin.certify_election(election_id) {
+                    Ok(_) => println!("✅ Election #{election_id} has been CERTIFIED."),
+                    Err(e) => println!("❌ Error: {e}"),
+                },
 
-                    match result {
-                        Ok((stored_user, stored_hash)) => {
-                            if verify_password(&stored_hash, &password) {
-                                println!("✅ Admin '{}' successfully logged in!", stored_user);
-                            } else {
-                                println!("❌ Incorrect password.");
-                            }
-                        }
-                        Err(_) => println!("⚠️ No such admin found."),
-                    }
-                }
             }
         }
 
@@ -435,6 +1538,71 @@ fn main() {
             print_elections(&conn);
         }
 
+        Some(Commands::VerifyReceipt { code }) => match vote::verify_receipt(&conn, &code) {
+            Ok((receipt, true)) => {
+                println!("✅ Ballot {} is in the published ballot list for election #{}.", receipt.tracking_code, receipt.election_id);
+                println!("   Fingerprint: {} (contents unchanged since it was cast)", receipt.fingerprint());
+            }
+            Ok((receipt, false)) => {
+                println!("❌ Ballot {} is listed for election #{}, but its contents no longer match the fingerprint {}.",
+                    receipt.tracking_code, receipt.election_id, receipt.fingerprint());
+            }
+            Err(e) => println!("❌ {e}"),
+        },
+
+        Some(Commands::PublishedBallots { election_id }) => match vote::published_ballots(&conn, election_id) {
+            Ok(list) => {
+                println!("\n📜 Published ballots for election #{election_id} ({} ballots):", list.len());
+                for r in list {
+                    println!(" {}  {}", r.tracking_code, r.content_hash);
+                }
+            }
+            Err(e) => println!("❌ Error listing ballots: {e}"),
+        },
+
+        Some(Commands::Audit(ac)) => {
+            let session = match auth::current_session(&conn, &auth::session_file(&cli.db)) {
+                Ok(s) => s,
+                Err(e) => {
+                    println!("❌ {e}");
+                    std::process::exit(1);
+                }
+            };
+            let admin = AdminService::new(&conn, &session);
+            match ac.sub {
+                AuditSub::Verify => match admin.verify_audit_log() {
+                    Ok((count, problems)) if problems.is_empty() => {
+                        println!("✅ Audit log intact: {count} entries verified.");
+                    }
+                    Ok((count, problems)) => {
+                        println!("❌ Audit log has been tampered with ({count} entries checked):");
+                        for p in problems {
+                            println!("   - {p}");
+                        }
+                        std::process::exit(1);
+                    }
+                    Err(e) => println!("❌ Error verifying audit log: {e}"),
+                },
+                AuditSub::Show { limit } => match admin.audit_log(limit) {
+                    Ok(entries) => {
+                        println!("\n🧾 Audit log (last {} entries):", entries.len());
+                        for e in entries {
+                            println!(" #{} {} [{}] {} {}", e.id, e.at, e.actor, e.action, e.detail);
+                            println!("      {}", e.hash.get(..16).unwrap_or(&e.hash));
+                        }
+                    }
+                    Err(e) => println!("❌ Error reading audit log: {e}"),
+                },
+            }
+        }
+
+        Some(Commands::Db(dc)) => match dc.sub {
+            DbSub::Migrate { status: true } => unreachable!("handled before migrating"),
+            DbSub::Migrate { status: false } => {
+                println!("✅ Database schema is at version {}.", db::latest_version());
+            }
+        },
+
         Some(Commands::Menu) => {
             interactive_menu(&conn);
         }
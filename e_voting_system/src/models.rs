@@ -9,17 +9,378 @@
 // ============================================================
 
 // src/models.rs
+use std::fmt;
+use std::str::FromStr;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use serde::{Deserialize, Serialize};
 
+/// Lifecycle of an election. Stored as text in `elections.status`.
+///
+/// Draft -> Open -> Closed -> Certified, with Open <-> Suspended and
+/// Cancelled reachable from any status before Closed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ElectionStatus {
+    Draft,
+    Open,
+    Suspended,
+    Closed,
+    Certified,
+    Cancelled,
+}
+
+impl ElectionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ElectionStatus::Draft => "Draft",
+            ElectionStatus::Open => "Open",
+            ElectionStatus::Suspended => "Suspended",
+            ElectionStatus::Closed => "Closed",
+            ElectionStatus::Certified => "Certified",
+            ElectionStatus::Cancelled => "Cancelled",
+        }
+    }
+
+    /// Whether the state machine allows moving from `self` to `next`.
+    pub fn can_transition_to(&self, next: ElectionStatus) -> bool {
+        use ElectionStatus::*;
+        matches!(
+            (self, next),
+            (Draft, Open)
+                | (Draft, Cancelled)
+                | (Open, Suspended)
+                | (Open, Closed)
+                | (Open, Cancelled)
+                | (Suspended, Open)
+                | (Suspended, Cancelled)
+                | (Closed, Certified)
+        )
+    }
+}
+
+impl fmt::Display for ElectionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ElectionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Draft" => Ok(ElectionStatus::Draft),
+            "Open" => Ok(ElectionStatus::Open),
+            "Suspended" => Ok(ElectionStatus::Suspended),
+            "Closed" => Ok(ElectionStatus::Closed),
+            "Certified" => Ok(ElectionStatus::Certified),
+            "Cancelled" => Ok(ElectionStatus::Cancelled),
+            other => Err(format!("unknown election status '{other}'")),
+        }
+    }
+}
+
+impl ToSql for ElectionStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for ElectionStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// How ballots for a position are marked and counted. Stored as text in
+/// `positions.method`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BallotMethod {
+    /// One candidate per voter, most votes wins
+    Plurality,
+    /// Voters rank candidates; counted by instant runoff
+    RankedChoice,
+    /// Voters rank candidates; several seats filled by single transferable vote
+    Stv,
+    /// Voters approve any number of candidates; most approvals wins
+    Approval,
+    /// Voters give each candidate 0..=max_score; highest total wins
+    Score,
+}
+
+impl BallotMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BallotMethod::Plurality => "Plurality",
+            BallotMethod::RankedChoice => "RankedChoice",
+            BallotMethod::Stv => "Stv",
+            BallotMethod::Approval => "Approval",
+            BallotMethod::Score => "Score",
+        }
+    }
+
+    /// Methods where the voter marks several candidates and the
+    /// min/max selection limits apply.
+    pub fn is_multi_select(&self) -> bool {
+        !matches!(self, BallotMethod::Plurality)
+    }
+}
+
+impl fmt::Display for BallotMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BallotMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "plurality" | "fptp" => Ok(BallotMethod::Plurality),
+            "rankedchoice" | "ranked" | "irv" => Ok(BallotMethod::RankedChoice),
+            "stv" => Ok(BallotMethod::Stv),
+            "approval" => Ok(BallotMethod::Approval),
+            "score" | "range" => Ok(BallotMethod::Score),
+            other => Err(format!("unknown ballot method '{other}'")),
+        }
+    }
+}
+
+impl ToSql for BallotMethod {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for BallotMethod {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// Share of the answers an option needs for a ballot question to carry.
+/// Stored as text in `questions.threshold`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PassThreshold {
+    /// More than half of the answers cast
+    SimpleMajority,
+    /// At least two thirds of the answers cast
+    TwoThirds,
+}
+
+impl PassThreshold {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PassThreshold::SimpleMajority => "SimpleMajority",
+            PassThreshold::TwoThirds => "TwoThirds",
+        }
+    }
+
+    /// Whether `votes` out of `total` answers meets the threshold.
+    pub fn is_met(&self, votes: u64, total: u64) -> bool {
+        match self {
+            PassThreshold::SimpleMajority => votes * 2 > total,
+            PassThreshold::TwoThirds => total > 0 && votes * 3 >= total * 2,
+        }
+    }
+}
+
+impl fmt::Display for PassThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PassThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "simplemajority" | "majority" => Ok(PassThreshold::SimpleMajority),
+            "twothirds" | "two-thirds" | "2/3" => Ok(PassThreshold::TwoThirds),
+            other => Err(format!("unknown pass threshold '{other}'")),
+        }
+    }
+}
+
+impl ToSql for PassThreshold {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for PassThreshold {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Election {
     pub id: i64,
     pub name: String,
-    pub status: String,
+    pub status: ElectionStatus,
     pub created_at: String,
 }
 
+/// How a position is voted and counted. Stored as columns on `positions`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct BallotRules {
+    pub method: BallotMethod,
+    /// Number of candidates elected to this position (only STV uses more than one)
+    pub seats: u32,
+    /// Fewest candidates an approval/score ballot must mark (unless abstaining)
+    pub min_selections: u32,
+    /// Most candidates an approval/score ballot may mark; 0 means no limit
+    pub max_selections: u32,
+    /// Highest score a score ballot may give
+    pub max_score: u32,
+    /// Whether voters may write in a name (plurality positions only)
+    pub allow_write_in: bool,
+}
+
+impl Default for BallotRules {
+    fn default() -> Self {
+        Self {
+            method: BallotMethod::Plurality,
+            seats: 1,
+            min_selections: 0,
+            max_selections: 0,
+            max_score: 5,
+            allow_write_in: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Position {
+    pub idx: i32,
+    pub title: String,
+    pub rules: BallotRules,
+}
+
+/// A ballot measure voted on alongside the positions of an election.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Question {
+    pub id: i64,
+    pub idx: i32,
+    pub text: String,
+    pub threshold: PassThreshold,
+    /// Percentage of registered voters who must answer for the result to count
+    pub quorum_percent: u32,
+    /// (option ID, label) in display order
+    pub options: Vec<(i64, String)>,
+}
+
+/// A voter's answer to one ballot question; `None` is an abstention.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuestionAnswer {
+    pub question_id: i64,
+    pub option_id: Option<i64>,
+}
+
+/// What a voter chose for a single position on their ballot.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum PositionChoice {
+    Abstain,
+    Candidate(i64),
+    /// Candidate IDs in order of preference, most preferred first
+    Ranked(Vec<i64>),
+    /// Every candidate the voter approves of
+    Approval(Vec<i64>),
+    /// (candidate ID, score) for every candidate on the position
+    Score(Vec<(i64, u32)>),
+    /// A name typed in by the voter, pending adjudication
+    WriteIn(String),
+}
+
+/// Where a write-in stands in the adjudication queue. Stored as text in
+/// `write_ins.status`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum WriteInStatus {
+    Pending,
+    /// Counted for an existing candidate
+    Merged,
+    /// Counted for a candidate created from the write-in
+    Created,
+    Rejected,
+}
+
+impl WriteInStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WriteInStatus::Pending => "Pending",
+            WriteInStatus::Merged => "Merged",
+            WriteInStatus::Created => "Created",
+            WriteInStatus::Rejected => "Rejected",
+        }
+    }
+}
+
+impl fmt::Display for WriteInStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WriteInStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(WriteInStatus::Pending),
+            "Merged" => Ok(WriteInStatus::Merged),
+            "Created" => Ok(WriteInStatus::Created),
+            "Rejected" => Ok(WriteInStatus::Rejected),
+            other => Err(format!("unknown write-in status '{other}'")),
+        }
+    }
+}
+
+impl ToSql for WriteInStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for WriteInStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// A write-in awaiting (or past) adjudication.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WriteIn {
+    pub id: i64,
+    pub position_idx: i32,
+    pub name: String,
+    pub status: WriteInStatus,
+    pub candidate_id: Option<i64>,
+}
+
+/// One position's worth of a ballot.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BallotEntry {
+    pub position_idx: i32,
+    pub choice: PositionChoice,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(dead_code)]
 pub struct Candidate {
     pub id: i64,
     pub name: String,
@@ -31,6 +392,214 @@ pub struct Candidate {
 #[allow(dead_code)]
 pub struct Voter {
     pub id: i64,
+    /// Printed on the voter's card and used to log in
+    pub voter_number: String,
     pub fullname: String,
     pub dob: String,
 }
+
+/// What a voter takes away after casting: a random tracking code and the
+/// SHA-256 digest binding it to the ballot's contents. Neither identifies
+/// the voter or reveals their choices.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Receipt {
+    pub election_id: i64,
+    pub tracking_code: String,
+    pub content_hash: String,
+}
+
+impl Receipt {
+    /// Short form of the content hash for voters to note down.
+    pub fn fingerprint(&self) -> &str {
+        &self.content_hash[..16.min(self.content_hash.len())]
+    }
+}
+
+/// What an admin account may do. An account can hold several roles.
+/// Stored as text in `admin_roles.role`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Everything, including managing other admins
+    SuperAdmin,
+    /// Sets up and runs elections, but cannot certify them
+    ElectionAdmin,
+    /// Registers voters and follows results
+    DistrictOfficial,
+    /// Read-only: results and the audit log
+    Auditor,
+}
+
+/// A single capability checked by `AdminService` before it acts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Create and edit elections, positions, questions and candidates
+    ManageElections,
+    /// Open, suspend, close and cancel elections
+    RunElections,
+    CertifyElections,
+    AdjudicateWriteIns,
+    ManageVoters,
+    ViewResults,
+    /// Grant roles and revoke other admins' sessions
+    ManageAdmins,
+    /// Read and verify the audit log
+    ViewAudit,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::SuperAdmin => "SuperAdmin",
+            Role::ElectionAdmin => "ElectionAdmin",
+            Role::DistrictOfficial => "DistrictOfficial",
+            Role::Auditor => "Auditor",
+        }
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        use Permission::*;
+        match self {
+            Role::SuperAdmin => true,
+            Role::ElectionAdmin => matches!(
+                permission,
+                ManageElections | RunElections | AdjudicateWriteIns | ManageVoters | ViewResults
+            ),
+            Role::DistrictOfficial => matches!(permission, ManageVoters | ViewResults),
+            Role::Auditor => matches!(permission, ViewResults | ViewAudit),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "superadmin" => Ok(Role::SuperAdmin),
+            "electionadmin" => Ok(Role::ElectionAdmin),
+            "districtofficial" | "district" => Ok(Role::DistrictOfficial),
+            "auditor" => Ok(Role::Auditor),
+            _ => Err(format!(
+                "unknown role '{s}' (expected super-admin, election-admin, district-official or auditor)"
+            )),
+        }
+    }
+}
+
+impl ToSql for Role {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Role {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Permission::ManageElections => "manage elections",
+            Permission::RunElections => "run elections",
+            Permission::CertifyElections => "certify elections",
+            Permission::AdjudicateWriteIns => "adjudicate write-ins",
+            Permission::ManageVoters => "manage voters",
+            Permission::ViewResults => "view results",
+            Permission::ManageAdmins => "manage admins",
+            Permission::ViewAudit => "view the audit log",
+        })
+    }
+}
+
+/// A logged-in admin. The token itself never leaves the session file; only
+/// its hash is stored in `admin_sessions`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AdminSession {
+    pub id: i64,
+    pub admin_id: i64,
+    pub username: String,
+    pub expires_at: String,
+}
+
+/// An admin account as shown by `admin user list`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AdminAccount {
+    pub id: i64,
+    pub username: String,
+    pub created_at: String,
+    /// None for the admin created by `init`
+    pub created_by: Option<String>,
+    pub disabled: bool,
+    pub roles: Vec<Role>,
+}
+
+/// Recent failed logins for one account or terminal (see lockout.rs).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lockout {
+    pub key: String,
+    pub failures: i64,
+    pub last_failure_at: String,
+    /// Set while the key must wait before trying again
+    pub locked_until: Option<String>,
+}
+
+/// A live single-use voting token (see token.rs). Only the hash of the
+/// printed code is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VotingToken {
+    pub id: i64,
+    pub voter_id: i64,
+    pub election_id: i64,
+    pub expires_at: String,
+}
+
+/// An electoral district and its polling stations (see district.rs).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct District {
+    pub id: i64,
+    pub name: String,
+    pub stations: Vec<PollingStation>,
+    /// Voters assigned to the district
+    pub voters: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollingStation {
+    pub id: i64,
+    pub district_id: i64,
+    pub name: String,
+}
+
+/// Registered voters, check-ins and ballots for one district of an
+/// election. `district_id` is None for voters and ballots with no district.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DistrictTurnout {
+    pub district_id: Option<i64>,
+    pub name: String,
+    pub registered: u64,
+    /// Voters from the district marked as having voted
+    pub voted: u64,
+    /// Ballots cast in the district
+    pub ballots: u64,
+}
+
+/// A voter as shown in a district official's poll book: who they are and
+/// whether they have checked in or voted, never what they voted for.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollBookEntry {
+    pub voter: Voter,
+    pub station: Option<String>,
+    pub checked_in_at: Option<String>,
+    pub voted: bool,
+}
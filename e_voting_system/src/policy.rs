@@ -0,0 +1,86 @@
+// ============================================================
+// File: policy.rs
+// Purpose: Rules new admin passwords and voter PINs must meet.
+//
+// Responsibilities:
+// - Check password length and mix of character classes
+// - Check PINs are the configured number of digits and not trivial
+// - Reject passwords on the local banned list or containing the username
+//
+// Limits come from the `password.*` and `pin.*` settings in config.rs.
+// Reuse of recent passwords is checked in auth.rs, which has the hashes.
+// Existing passwords keep working; the rules apply whenever one is set.
+// ============================================================
+
+use rusqlite::Connection;
+
+use crate::config;
+use crate::error::{AppError, AppResult};
+
+/// Common passwords, shipped with the program so checks work offline.
+const BANNED: &str = include_str!("banned_passwords.txt");
+
+fn is_banned(password: &str) -> bool {
+    let password = password.to_lowercase();
+    BANNED
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .any(|banned| banned == password)
+}
+
+/// Check a new admin password. All problems are reported together.
+pub fn check_password(conn: &Connection, username: &str, password: &str) -> AppResult<()> {
+    let min_length = config::get(conn, "password.min_length")?;
+    let min_classes = config::get(conn, "password.min_classes")?;
+    let mut problems = Vec::new();
+
+    if (password.chars().count() as i64) < min_length {
+        problems.push(format!("be at least {min_length} characters long"));
+    }
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .iter()
+    .filter(|&&present| present)
+    .count() as i64;
+    if classes < min_classes {
+        problems.push(format!(
+            "mix at least {min_classes} of lowercase, uppercase, digits and symbols"
+        ));
+    }
+    let username = username.trim().to_lowercase();
+    if !username.is_empty() && password.to_lowercase().contains(&username) {
+        problems.push("not contain the username".to_string());
+    }
+    if is_banned(password) {
+        problems.push("not be a commonly used password".to_string());
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Invalid(format!("password must {}", problems.join(", "))))
+    }
+}
+
+/// Check a new voter PIN: exactly the configured number of digits, and not
+/// one repeated digit or a run such as 123456.
+pub fn check_pin(conn: &Connection, pin: &str) -> AppResult<()> {
+    let digits = config::get(conn, "pin.digits")?;
+    if pin.chars().count() as i64 != digits || !pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err(AppError::Invalid(format!("PIN must be exactly {digits} digits")));
+    }
+    let steps: Vec<i32> = pin
+        .as_bytes()
+        .windows(2)
+        .map(|w| w[1] as i32 - w[0] as i32)
+        .collect();
+    if steps.iter().all(|&s| s == 0) || steps.iter().all(|&s| s == 1) || steps.iter().all(|&s| s == -1) {
+        return Err(AppError::Invalid("PIN must not be a repeated digit or a simple sequence".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+// ============================================================
+// File: sealing.rs
+// Purpose: Encrypt small secrets at rest under a password.
+//
+// Responsibilities:
+// - Seal a secret with a key derived from a password and a fresh salt
+// - Open it again with the same password
+//
+// Used for admins' two-factor secrets (see auth.rs) and offline stations'
+// signing keys (see station.rs). Secrets are sealed with ChaCha20-Poly1305
+// under an Argon2id key from the password and a per-secret salt, so a
+// copy of the database alone does not reveal them.
+// ============================================================
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::error::{AppError, AppResult};
+
+/// Fixed rather than taken from the `argon2.*` settings: changing those must
+/// not make sealed secrets unreadable.
+fn derive_key(password: &str, salt: &[u8]) -> AppResult<Key> {
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::DEFAULT)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| AppError::Invalid(format!("could not derive key: {e}")))?;
+    Ok(Key::from(key))
+}
+
+/// Encrypt a secret under a password. Returns (sealed, salt), both base64,
+/// for storage.
+pub fn seal(secret: &[u8], password: &str) -> AppResult<(String, String)> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, &salt)?);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        cipher
+            .encrypt(&Nonce::from(nonce), secret)
+            .map_err(|_| AppError::Invalid("could not encrypt secret".to_string()))?,
+    );
+    Ok((STANDARD.encode(sealed), STANDARD.encode(salt)))
+}
+
+/// Reverse of `seal`. `None` if the password is wrong or the data was
+/// altered, which cannot be told apart; an error naming `what` if the
+/// stored value is not a sealed secret at all.
+pub fn open(sealed: &str, salt: &str, password: &str, what: &str) -> AppResult<Option<Vec<u8>>> {
+    let corrupt = || AppError::Invalid(format!("stored {what} is corrupt"));
+    let sealed = STANDARD.decode(sealed).map_err(|_| corrupt())?;
+    let salt = STANDARD.decode(salt).map_err(|_| corrupt())?;
+    if sealed.len() < 12 {
+        return Err(corrupt());
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let nonce: [u8; 12] = nonce.try_into().map_err(|_| corrupt())?;
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, &salt)?);
+    Ok(cipher.decrypt(&Nonce::from(nonce), ciphertext).ok())
+}
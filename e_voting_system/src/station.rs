@@ -0,0 +1,769 @@
+// ============================================================
+// File: station.rs
+// Purpose: Offline polling stations and the signed result packages they
+//          send to the central instance.
+//
+// Responsibilities:
+// - Give a station database its own Ed25519 signing identity, with the
+//   secret key encrypted under a station passphrase
+// - Export a closed local ballot box as a signed result package
+// - Verify and import packages centrally, once per station and election
+//
+// A station runs its own copy of the database with the central roll and
+// ballot. When its box is closed it exports its anonymous ballots, their
+// tallies, who voted and a hash of the box's receipts; the JSON payload is
+// signed as-is, so the central side verifies the exact bytes that were
+// signed. Central only accepts packages signed by the key it trusts for
+// that station, and each station reports each election once. Imported
+// ballots join the central box under the station's district and station,
+// so every count includes them, and voters in the participation list count
+// as having voted centrally. Write-ins travel unadjudicated and are
+// decided centrally.
+// ============================================================
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::district;
+use crate::election::{election_status, require_status};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    BallotEntry, BallotMethod, ElectionStatus, Position, PositionChoice, Question, QuestionAnswer, WriteInStatus,
+};
+use crate::policy;
+use crate::sealing;
+use crate::vote::{
+    ballot_digest, check_ballot, has_voted, list_candidates, list_positions, list_questions, random_code, random_id, to_hex,
+};
+
+/// Written into every package so a future layout change is recognised.
+pub const FORMAT: &str = "rusttrust-station-results/2";
+
+/// What a station reports for one election. Serialized to JSON and signed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StationReport {
+    pub package_id: String,
+    pub election_id: i64,
+    pub election_name: String,
+    pub district: String,
+    pub station: String,
+    pub exported_at: String,
+    pub ballot_count: u64,
+    /// SHA-256 over the receipts of the ballots below (see `ballot_box_hash`)
+    pub ballot_box_hash: String,
+    /// Every ballot in the box, in tracking-code order
+    #[serde(default)]
+    pub ballots: Vec<StationBallot>,
+    /// Voter numbers of everyone who voted at the station
+    pub participation: Vec<String>,
+    pub positions: Vec<PositionTally>,
+    pub questions: Vec<QuestionTally>,
+}
+
+/// One anonymous ballot as the voter cast it, with the receipt they were
+/// given. Votes added by write-in adjudication are not included.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StationBallot {
+    pub tracking_code: String,
+    pub content_hash: String,
+    /// (position index, candidate ID, rank, score)
+    pub votes: Vec<(i32, i64, Option<i32>, Option<u32>)>,
+    /// (question ID, option ID)
+    pub answers: Vec<(i64, i64)>,
+    /// (position index, name)
+    pub write_ins: Vec<(i32, String)>,
+}
+
+impl StationReport {
+    /// Tracking code and content hash of every ballot.
+    pub fn receipts(&self) -> Vec<(String, String)> {
+        self.ballots.iter().map(|b| (b.tracking_code.clone(), b.content_hash.clone())).collect()
+    }
+}
+
+/// Marks per candidate for one position, counted as in district results:
+/// first preferences for ranked methods, points for score.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PositionTally {
+    pub idx: i32,
+    pub title: String,
+    pub method: String,
+    pub candidates: Vec<CandidateTally>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CandidateTally {
+    pub candidate_id: i64,
+    pub name: String,
+    pub party: String,
+    pub marks: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuestionTally {
+    pub question_id: i64,
+    pub text: String,
+    pub options: Vec<(i64, String, u64)>,
+}
+
+/// The file handed to central: the payload exactly as signed.
+#[derive(Debug, Serialize, Deserialize)]
+struct SignedPackage {
+    format: String,
+    payload: String,
+    signature: String,
+}
+
+/// A package accepted by the central instance.
+#[derive(Debug, Clone)]
+pub struct ImportedResult {
+    pub report: StationReport,
+    pub imported_at: String,
+}
+
+/// SHA-256 over "tracking_code:content_hash" lines in tracking-code order,
+/// so any added, removed or changed ballot changes the hash.
+pub fn ballot_box_hash(receipts: &[(String, String)]) -> String {
+    let mut sorted: Vec<&(String, String)> = receipts.iter().collect();
+    sorted.sort();
+    let mut hasher = Sha256::new();
+    for (code, hash) in sorted {
+        hasher.update(format!("{code}:{hash}\n").as_bytes());
+    }
+    to_hex(&hasher.finalize())
+}
+
+/// Marks per candidate for a position across `ballots`, counted like
+/// `district::position_totals`: first preferences for ranked methods,
+/// points for score, one mark otherwise.
+fn count_marks(ballots: &[StationBallot], position_idx: i32, method: BallotMethod) -> HashMap<i64, u64> {
+    let mut marks: HashMap<i64, u64> = HashMap::new();
+    for (_, cid, rank, score) in ballots.iter().flat_map(|b| &b.votes).filter(|v| v.0 == position_idx) {
+        let n = match method {
+            BallotMethod::Score => score.unwrap_or(0) as u64,
+            _ if rank.is_none() || *rank == Some(1) => 1,
+            _ => 0,
+        };
+        *marks.entry(*cid).or_default() += n;
+    }
+    marks
+}
+
+/// Answers per option across `ballots`.
+fn count_answers(ballots: &[StationBallot], question_id: i64) -> HashMap<i64, u64> {
+    let mut counts: HashMap<i64, u64> = HashMap::new();
+    for (_, oid) in ballots.iter().flat_map(|b| &b.answers).filter(|a| a.0 == question_id) {
+        *counts.entry(*oid).or_default() += 1;
+    }
+    counts
+}
+
+/// Read one ballot from the local box, as `vote::ballot_digest` sees it.
+fn load_ballot(conn: &Connection, ballot_id: i64, tracking_code: String, content_hash: String) -> rusqlite::Result<StationBallot> {
+    let mut stmt = conn.prepare(
+        "SELECT position_idx, candidate_id, rank, score FROM votes WHERE ballot_id=?1 AND write_in_id IS NULL
+         ORDER BY position_idx, rank, candidate_id",
+    )?;
+    let votes = stmt
+        .query_map(params![ballot_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut stmt = conn.prepare("SELECT question_id, option_id FROM answers WHERE ballot_id=?1 ORDER BY question_id")?;
+    let answers = stmt.query_map(params![ballot_id], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<rusqlite::Result<_>>()?;
+    let mut stmt = conn.prepare("SELECT position_idx, name FROM write_ins WHERE ballot_id=?1 ORDER BY position_idx")?;
+    let write_ins = stmt.query_map(params![ballot_id], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<rusqlite::Result<_>>()?;
+    Ok(StationBallot { tracking_code, content_hash, votes, answers, write_ins })
+}
+
+fn parse_public_key(encoded: &str) -> AppResult<VerifyingKey> {
+    let invalid = || AppError::Invalid("not a valid station public key".to_string());
+    let bytes: [u8; 32] = STANDARD.decode(encoded.trim()).map_err(|_| invalid())?.try_into().map_err(|_| invalid())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| invalid())
+}
+
+// ------------------ Station side ------------------
+
+/// Make this database the given polling station and generate its signing
+/// key. Returns the public key to register with central. The secret key is
+/// stored encrypted under `passphrase`, which every export asks for again.
+pub fn init_identity(conn: &Connection, station_id: i64, passphrase: &str) -> AppResult<String> {
+    if identity(conn)?.is_some() {
+        return Err(AppError::Invalid("this database already has a station identity".to_string()));
+    }
+    check_passphrase(conn, passphrase)?;
+    let key = SigningKey::generate(&mut OsRng);
+    let public_key = STANDARD.encode(key.verifying_key().to_bytes());
+    let (sealed, salt) = sealing::seal(&key.to_bytes(), passphrase)?;
+    conn.execute(
+        "INSERT INTO station_identity (id, station_id, public_key, secret_key, secret_salt, created_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5)",
+        params![station_id, public_key, sealed, salt, Utc::now().to_rfc3339()],
+    )?;
+    Ok(public_key)
+}
+
+/// The station this database runs as and its public key, if any.
+pub fn identity(conn: &Connection) -> rusqlite::Result<Option<(i64, String)>> {
+    conn.query_row("SELECT station_id, public_key FROM station_identity WHERE id=1", [], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .optional()
+}
+
+/// A new station passphrase must meet the admin password policy.
+fn check_passphrase(conn: &Connection, passphrase: &str) -> AppResult<()> {
+    policy::check_password(conn, "", passphrase).map_err(|e| match e {
+        AppError::Invalid(why) => AppError::Invalid(why.replacen("password", "station passphrase", 1)),
+        e => e,
+    })
+}
+
+/// Decrypt the station's signing key.
+fn signing_key(conn: &Connection, passphrase: &str) -> AppResult<(i64, SigningKey)> {
+    let (station_id, secret, salt): (i64, String, String) = conn
+        .query_row("SELECT station_id, secret_key, secret_salt FROM station_identity WHERE id=1", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .optional()?
+        .ok_or_else(|| AppError::Invalid("this database is not a polling station; see `admin station init`".to_string()))?;
+    let bytes = sealing::open(&secret, &salt, passphrase, "station key")?
+        .ok_or_else(|| AppError::Unauthenticated("incorrect station passphrase".to_string()))?;
+    let bytes: [u8; 32] =
+        bytes.try_into().map_err(|_| AppError::Invalid("stored station key is corrupt".to_string()))?;
+    Ok((station_id, SigningKey::from_bytes(&bytes)))
+}
+
+/// District and station names of a station.
+pub fn station_names(conn: &Connection, station_id: i64) -> rusqlite::Result<(String, String)> {
+    conn.query_row(
+        "SELECT d.name, s.name FROM polling_stations s JOIN districts d ON d.id = s.district_id WHERE s.id=?1",
+        params![station_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Count the local box of a closed election and sign the result with the
+/// key unlocked by `passphrase`. Returns the report and the package to
+/// hand to central.
+pub fn export(conn: &Connection, election_id: i64, passphrase: &str) -> AppResult<(StationReport, String)> {
+    let (station_id, key) = signing_key(conn, passphrase)?;
+    require_status(conn, election_id, &[ElectionStatus::Closed], "export station results")?;
+    let (district, station) = station_names(conn, station_id)?;
+    let election_name: String =
+        conn.query_row("SELECT name FROM elections WHERE id=?1", params![election_id], |row| row.get(0))?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, tracking_code, content_hash FROM ballots
+         WHERE election_id=?1 AND tracking_code IS NOT NULL ORDER BY tracking_code",
+    )?;
+    let rows: Vec<(i64, String, String)> = stmt
+        .query_map(params![election_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut ballots = Vec::new();
+    for (ballot_id, tracking_code, content_hash) in rows {
+        ballots.push(load_ballot(conn, ballot_id, tracking_code, content_hash)?);
+    }
+
+    let candidates = list_candidates(conn, election_id)?;
+    let mut positions = Vec::new();
+    for position in list_positions(conn, election_id)? {
+        let totals = count_marks(&ballots, position.idx, position.rules.method);
+        let candidates = candidates
+            .iter()
+            .filter(|(_, _, _, pidx)| *pidx == position.idx)
+            .map(|(cid, name, party, _)| CandidateTally {
+                candidate_id: *cid,
+                name: name.clone(),
+                party: party.clone(),
+                marks: totals.get(cid).copied().unwrap_or(0),
+            })
+            .collect();
+        positions.push(PositionTally {
+            idx: position.idx,
+            title: position.title.clone(),
+            method: position.rules.method.to_string(),
+            candidates,
+        });
+    }
+
+    let mut questions = Vec::new();
+    for question in list_questions(conn, election_id)? {
+        let counts = count_answers(&ballots, question.id);
+        let options = question
+            .options
+            .iter()
+            .map(|(oid, label)| (*oid, label.clone(), counts.get(oid).copied().unwrap_or(0)))
+            .collect();
+        questions.push(QuestionTally { question_id: question.id, text: question.text.clone(), options });
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT v.voter_number FROM participation p JOIN voters v ON v.id = p.voter_id
+         WHERE p.election_id=?1 ORDER BY v.voter_number",
+    )?;
+    let participation: Vec<String> = stmt.query_map(params![election_id], |row| row.get(0))?.filter_map(|r| r.ok()).collect();
+
+    let mut report = StationReport {
+        package_id: random_code(16),
+        election_id,
+        election_name,
+        district,
+        station,
+        exported_at: Utc::now().to_rfc3339(),
+        ballot_count: ballots.len() as u64,
+        ballot_box_hash: String::new(),
+        ballots,
+        participation,
+        positions,
+        questions,
+    };
+    report.ballot_box_hash = ballot_box_hash(&report.receipts());
+    let payload = serde_json::to_string(&report).map_err(|e| AppError::Invalid(format!("could not encode results: {e}")))?;
+    let package = SignedPackage {
+        format: FORMAT.to_string(),
+        signature: STANDARD.encode(key.sign(payload.as_bytes()).to_bytes()),
+        payload,
+    };
+    let package = serde_json::to_string_pretty(&package).map_err(|e| AppError::Invalid(format!("could not encode package: {e}")))?;
+    Ok((report, package))
+}
+
+// ------------------ Central side ------------------
+
+/// Trust `public_key` as the signing key of a polling station, replacing
+/// any key trusted for it before.
+pub fn trust(conn: &Connection, station_id: i64, public_key: &str, trusted_by: i64) -> AppResult<()> {
+    parse_public_key(public_key)?;
+    conn.execute(
+        "INSERT INTO station_keys (station_id, public_key, trusted_by, trusted_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(station_id) DO UPDATE SET public_key=excluded.public_key, trusted_by=excluded.trusted_by,
+             trusted_at=excluded.trusted_at",
+        params![station_id, public_key.trim(), trusted_by, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Check a package's signature against the key trusted for the station it
+/// names, and that its contents agree with themselves. Returns the district
+/// and station IDs and the report.
+fn verify(conn: &Connection, package: &str) -> AppResult<(i64, i64, StationReport)> {
+    let altered = |why: &str| AppError::Invalid(format!("rejected station package: {why}"));
+    let package: SignedPackage = serde_json::from_str(package).map_err(|_| altered("not a station result package"))?;
+    if package.format != FORMAT {
+        return Err(altered(&format!("unsupported format '{}'", package.format)));
+    }
+    let report: StationReport = serde_json::from_str(&package.payload).map_err(|_| altered("payload is not readable"))?;
+
+    let district_id = district::find_district(conn, &report.district)?;
+    let station_id = district::find_station(conn, district_id, &report.station)?;
+    let public_key: String = conn
+        .query_row("SELECT public_key FROM station_keys WHERE station_id=?1", params![station_id], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| altered(&format!("no key is trusted for station '{}' in '{}'", report.station, report.district)))?;
+    let signature: [u8; 64] = STANDARD
+        .decode(&package.signature)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| altered("signature is malformed"))?;
+    parse_public_key(&public_key)?
+        .verify(package.payload.as_bytes(), &Signature::from_bytes(&signature))
+        .map_err(|_| altered("signature does not match; the package was altered or signed by another key"))?;
+
+    if ballot_box_hash(&report.receipts()) != report.ballot_box_hash {
+        return Err(altered("ballot box hash does not match its receipts"));
+    }
+    if report.ballots.len() as u64 != report.ballot_count {
+        return Err(altered(&format!("{} ballot(s) reported but {} included", report.ballot_count, report.ballots.len())));
+    }
+    for tally in &report.positions {
+        let method: BallotMethod = tally.method.parse().map_err(|_| altered(&format!("unknown method '{}'", tally.method)))?;
+        let mut counted = count_marks(&report.ballots, tally.idx, method);
+        counted.retain(|_, n| *n > 0);
+        let mut reported: HashMap<i64, u64> = tally.candidates.iter().map(|c| (c.candidate_id, c.marks)).collect();
+        reported.retain(|_, n| *n > 0);
+        if counted != reported {
+            return Err(altered(&format!("tallies for position {} do not match its ballots", tally.idx)));
+        }
+    }
+    for tally in &report.questions {
+        let counted = count_answers(&report.ballots, tally.question_id);
+        if tally.options.iter().any(|(oid, _, n)| counted.get(oid).copied().unwrap_or(0) != *n)
+            || counted.keys().any(|oid| !tally.options.iter().any(|(id, _, _)| id == oid))
+        {
+            return Err(altered(&format!("tallies for question #{} do not match its ballots", tally.question_id)));
+        }
+    }
+    if report.participation.len() as u64 != report.ballot_count {
+        return Err(altered(&format!(
+            "{} ballot(s) but {} voter(s) in the participation list",
+            report.ballot_count,
+            report.participation.len()
+        )));
+    }
+    Ok((district_id, station_id, report))
+}
+
+/// Verify a package and record it: its ballots join the central box and
+/// the voters in it are marked as having voted. Refuses a second package
+/// from the same station for the same election, and any package whose
+/// ballot or voters do not match the central records.
+pub fn import(conn: &Connection, package: &str, imported_by: i64) -> AppResult<StationReport> {
+    let (district_id, station_id, report) = verify(conn, package)?;
+    let election_id = report.election_id;
+    let tx = conn.unchecked_transaction()?;
+
+    let status = election_status(&tx, election_id)?;
+    if !matches!(status, ElectionStatus::Open | ElectionStatus::Closed) {
+        return Err(AppError::WrongStatus { election_id, status, action: "import station results" });
+    }
+    let name: String = tx.query_row("SELECT name FROM elections WHERE id=?1", params![election_id], |row| row.get(0))?;
+    if name != report.election_name {
+        return Err(AppError::Invalid(format!(
+            "package is for '{}' but election #{election_id} is '{name}'",
+            report.election_name
+        )));
+    }
+    let already: Option<String> = tx
+        .query_row(
+            "SELECT package_id FROM station_results WHERE election_id=?1 AND station_id=?2",
+            params![election_id, station_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(package_id) = already {
+        let what = if package_id == report.package_id { "this package was" } else { "results were" };
+        return Err(AppError::Invalid(format!(
+            "{what} already imported for station '{}' in election #{election_id}",
+            report.station
+        )));
+    }
+
+    let candidates = list_candidates(&tx, election_id)?;
+    let positions = list_positions(&tx, election_id)?;
+    for tally in &report.positions {
+        if !positions.iter().any(|p| p.idx == tally.idx && p.title == tally.title) {
+            return Err(AppError::Invalid(format!("position {} '{}' is not on the central ballot", tally.idx, tally.title)));
+        }
+        for c in &tally.candidates {
+            if !candidates.iter().any(|(cid, name, _, pidx)| *cid == c.candidate_id && *name == c.name && *pidx == tally.idx) {
+                return Err(AppError::Invalid(format!(
+                    "candidate #{} '{}' is not on the central ballot for position {}",
+                    c.candidate_id, c.name, tally.idx
+                )));
+            }
+        }
+    }
+
+    let questions = list_questions(&tx, election_id)?;
+    for tally in &report.questions {
+        let question = questions
+            .iter()
+            .find(|q| q.id == tally.question_id && q.text == tally.text)
+            .ok_or_else(|| {
+                AppError::Invalid(format!("question #{} '{}' is not on the central ballot", tally.question_id, tally.text))
+            })?;
+        for (oid, label, _) in &tally.options {
+            if !question.options.iter().any(|(id, l)| id == oid && l == label) {
+                return Err(AppError::Invalid(format!(
+                    "option #{oid} '{label}' does not belong to question #{}",
+                    tally.question_id
+                )));
+            }
+        }
+    }
+
+    tx.execute(
+        "INSERT INTO station_results (election_id, station_id, package_id, payload, ballot_count, imported_by, imported_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            election_id,
+            station_id,
+            report.package_id,
+            serde_json::to_string(&report).map_err(|e| AppError::Invalid(format!("could not store results: {e}")))?,
+            report.ballot_count as i64,
+            imported_by,
+            Utc::now().to_rfc3339()
+        ],
+    )?;
+    let result_id = tx.last_insert_rowid();
+
+    // Checked like a ballot cast by a voter of the station's district.
+    let positions = district::district_positions(&tx, election_id, Some(district_id))?;
+    for ballot in &report.ballots {
+        store_ballot(&tx, election_id, (district_id, station_id), ballot, &candidates, &positions, &questions)?;
+    }
+
+    let mut unknown = Vec::new();
+    let mut voted = Vec::new();
+    for number in &report.participation {
+        let voter_id: Option<i64> = tx
+            .query_row("SELECT id FROM voters WHERE voter_number=?1", params![number], |row| row.get(0))
+            .optional()?;
+        let Some(voter_id) = voter_id else {
+            unknown.push(number.as_str());
+            continue;
+        };
+        if has_voted(&tx, voter_id, election_id) {
+            voted.push(number.as_str());
+            continue;
+        }
+        tx.execute(
+            "INSERT INTO station_participation (election_id, voter_id, result_id) VALUES (?1, ?2, ?3)",
+            params![election_id, voter_id, result_id],
+        )?;
+    }
+    if !unknown.is_empty() {
+        return Err(AppError::Invalid(format!("voter(s) not on the central roll: {}", unknown.join(", "))));
+    }
+    if !voted.is_empty() {
+        return Err(AppError::Invalid(format!("voter(s) who already voted elsewhere: {}", voted.join(", "))));
+    }
+
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Rebuild the choices a station ballot records, so it can be checked with
+/// `vote::check_ballot` like a ballot cast here.
+fn ballot_choices(ballot: &StationBallot, positions: &[Position]) -> AppResult<(Vec<BallotEntry>, Vec<QuestionAnswer>)> {
+    let mut marked: Vec<i32> = ballot.votes.iter().map(|v| v.0).chain(ballot.write_ins.iter().map(|w| w.0)).collect();
+    marked.sort();
+    marked.dedup();
+
+    let mut entries = Vec::new();
+    for pidx in marked {
+        let position = positions
+            .iter()
+            .find(|p| p.idx == pidx)
+            .ok_or_else(|| AppError::Invalid(format!("position {pidx} is not on the ballot in this district")))?;
+        let method = position.rules.method;
+        let malformed = || AppError::Invalid(format!("position {pidx} is not marked the way a {method} ballot is"));
+        let mut votes: Vec<_> = ballot.votes.iter().filter(|v| v.0 == pidx).collect();
+        let write_ins: Vec<&String> = ballot.write_ins.iter().filter(|w| w.0 == pidx).map(|w| &w.1).collect();
+        votes.sort_by_key(|v| v.2);
+        let choice = match (method, votes.as_slice(), write_ins.as_slice()) {
+            (_, [], [name]) => PositionChoice::WriteIn(name.to_string()),
+            (_, _, [_, ..]) => return Err(malformed()),
+            (BallotMethod::Plurality, [(_, cid, None, None)], _) => PositionChoice::Candidate(*cid),
+            (BallotMethod::RankedChoice | BallotMethod::Stv, votes, _)
+                if votes.iter().enumerate().all(|(i, v)| v.2 == Some(i as i32 + 1) && v.3.is_none()) =>
+            {
+                PositionChoice::Ranked(votes.iter().map(|v| v.1).collect())
+            }
+            (BallotMethod::Approval, votes, _) if votes.iter().all(|v| v.2.is_none() && v.3.is_none()) => {
+                PositionChoice::Approval(votes.iter().map(|v| v.1).collect())
+            }
+            (BallotMethod::Score, votes, _) if votes.iter().all(|v| v.2.is_none() && v.3.is_some()) => {
+                PositionChoice::Score(votes.iter().map(|v| (v.1, v.3.unwrap_or(0))).collect())
+            }
+            _ => return Err(malformed()),
+        };
+        entries.push(BallotEntry { position_idx: pidx, choice });
+    }
+    let answers = ballot.answers.iter().map(|(qid, oid)| QuestionAnswer { question_id: *qid, option_id: Some(*oid) }).collect();
+    Ok((entries, answers))
+}
+
+/// Add one station ballot to the central box, placed at its station, after
+/// the same checks as a ballot cast here, and check its contents still
+/// produce the content hash on its receipt.
+fn store_ballot(
+    conn: &Connection,
+    election_id: i64,
+    (district_id, station_id): (i64, i64),
+    ballot: &StationBallot,
+    candidates: &[(i64, String, String, i32)],
+    positions: &[Position],
+    questions: &[Question],
+) -> AppResult<()> {
+    let code = &ballot.tracking_code;
+    ballot_choices(ballot, positions)
+        .and_then(|(entries, answers)| check_ballot(positions, candidates, questions, &entries, &answers))
+        .map_err(|e| match e {
+            AppError::Invalid(why) => AppError::Invalid(format!("ballot {code}: {why}")),
+            e => e,
+        })?;
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM ballots WHERE tracking_code=?1)", params![code], |row| row.get(0))?;
+    if taken {
+        return Err(AppError::Invalid(format!("a ballot with tracking code {code} is already in the box")));
+    }
+
+    let ballot_id = random_id(conn, "ballots")?;
+    conn.execute(
+        "INSERT INTO ballots (id, election_id, district_id, station_id, tracking_code, content_hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![ballot_id, election_id, district_id, station_id, code, ballot.content_hash],
+    )?;
+    for (pidx, cid, rank, score) in &ballot.votes {
+        conn.execute(
+            "INSERT INTO votes (id, ballot_id, election_id, position_idx, candidate_id, rank, score) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![random_id(conn, "votes")?, ballot_id, election_id, pidx, cid, rank, score],
+        )?;
+    }
+    for (qid, oid) in &ballot.answers {
+        conn.execute(
+            "INSERT INTO answers (id, ballot_id, election_id, question_id, option_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![random_id(conn, "answers")?, ballot_id, election_id, qid, oid],
+        )?;
+    }
+    for (pidx, name) in &ballot.write_ins {
+        conn.execute(
+            "INSERT INTO write_ins (id, ballot_id, election_id, position_idx, name, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![random_id(conn, "write_ins")?, ballot_id, election_id, pidx, name, WriteInStatus::Pending],
+        )?;
+    }
+    if ballot_digest(conn, ballot_id, code)? != ballot.content_hash {
+        return Err(AppError::Invalid(format!("ballot {code} does not match the content hash on its receipt")));
+    }
+    Ok(())
+}
+
+/// Stations with a trusted key that have not yet sent results for the
+/// election, as (district, station) names.
+pub fn outstanding(conn: &Connection, election_id: i64) -> rusqlite::Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT d.name, s.name FROM station_keys k
+         JOIN polling_stations s ON s.id = k.station_id JOIN districts d ON d.id = s.district_id
+         WHERE NOT EXISTS(SELECT 1 FROM station_results r WHERE r.election_id=?1 AND r.station_id=k.station_id)
+         ORDER BY d.name, s.name",
+    )?;
+    let rows = stmt.query_map(params![election_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Packages imported for an election, by station name.
+pub fn imported(conn: &Connection, election_id: i64) -> AppResult<Vec<ImportedResult>> {
+    let mut stmt = conn.prepare(
+        "SELECT r.payload, r.imported_at FROM station_results r JOIN polling_stations s ON s.id = r.station_id
+         WHERE r.election_id=?1 ORDER BY s.name",
+    )?;
+    let rows = stmt.query_map(params![election_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    let mut results = Vec::new();
+    for row in rows {
+        let (payload, imported_at) = row?;
+        let report = serde_json::from_str(&payload)
+            .map_err(|_| AppError::Invalid(format!("stored station results for election #{election_id} are corrupt")))?;
+        results.push(ImportedResult { report, imported_at });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::vote::cast_ballot;
+
+    const PASSPHRASE: &str = "Station-S1 key#2026";
+
+    /// Election #1 with a plurality race for Mayor (Alice #1, Bob #2) and
+    /// two voters at station S1 in South, in the given status.
+    fn election_db(status: ElectionStatus) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        conn.execute_batch(&format!(
+            r#"
+            INSERT INTO admins (id, username, password_hash, created_at) VALUES (1, 'chief', 'x', '2026-01-01');
+            INSERT INTO elections (id, name, status, created_at) VALUES (1, 'Council', '{status}', '2026-01-01');
+            INSERT INTO positions (election_id, idx, title) VALUES (1, 0, 'Mayor');
+            INSERT INTO candidates (id, election_id, position_idx, name, party) VALUES (1, 1, 0, 'Alice', 'Red'), (2, 1, 0, 'Bob', 'Blue');
+            INSERT INTO districts (id, name, created_at) VALUES (1, 'South', '2026-01-01');
+            INSERT INTO polling_stations (id, district_id, name) VALUES (1, 1, 'S1');
+            INSERT INTO voters (id, voter_number, fullname, dob, pinhash, district_id, station_id) VALUES
+                (1, 'AAAA-AAAA', 'Ann', '1990-01-01', 'x', 1, 1),
+                (2, 'BBBB-BBBB', 'Ben', '1990-01-01', 'x', 1, 1);
+        "#
+        ))
+        .unwrap();
+        conn
+    }
+
+    /// A station where Ann voted for Alice and Ben for Bob, its exported
+    /// package, and a central database that trusts the station's key.
+    fn exported() -> (Connection, String, Connection) {
+        let station = election_db(ElectionStatus::Open);
+        let public_key = init_identity(&station, 1, PASSPHRASE).unwrap();
+        for (voter_id, candidate_id) in [(1, 1), (2, 2)] {
+            let entry = BallotEntry { position_idx: 0, choice: PositionChoice::Candidate(candidate_id) };
+            cast_ballot(&station, 1, voter_id, None, &[entry], &[]).unwrap();
+        }
+        station.execute("UPDATE elections SET status=?1 WHERE id=1", params![ElectionStatus::Closed]).unwrap();
+        let (_, package) = export(&station, 1, PASSPHRASE).unwrap();
+
+        let central = election_db(ElectionStatus::Closed);
+        trust(&central, 1, &public_key, 1).unwrap();
+        (station, package, central)
+    }
+
+    /// Change the report in a package and sign it again with the station's
+    /// own key, as a station running altered software could.
+    fn resign(station: &Connection, package: &str, change: impl FnOnce(&mut StationReport)) -> String {
+        let mut package: SignedPackage = serde_json::from_str(package).unwrap();
+        let mut report: StationReport = serde_json::from_str(&package.payload).unwrap();
+        change(&mut report);
+        let (_, key) = signing_key(station, PASSPHRASE).unwrap();
+        package.payload = serde_json::to_string(&report).unwrap();
+        package.signature = STANDARD.encode(key.sign(package.payload.as_bytes()).to_bytes());
+        serde_json::to_string(&package).unwrap()
+    }
+
+    fn import_error(central: &Connection, package: &str) -> String {
+        import(central, package, 1).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn package_imports_once() {
+        let (_, package, central) = exported();
+        let report = import(&central, &package, 1).unwrap();
+        assert_eq!(report.ballot_count, 2);
+        let counted: u64 =
+            central.query_row("SELECT COUNT(*) FROM ballots WHERE station_id=1", [], |row| row.get(0)).unwrap();
+        assert_eq!(counted, 2);
+        assert!(import_error(&central, &package).contains("this package was already imported"));
+    }
+
+    #[test]
+    fn altered_payload_is_rejected() {
+        let (_, package, central) = exported();
+        let altered = package.replace(r#"\"marks\":1"#, r#"\"marks\":2"#);
+        assert_ne!(altered, package);
+        assert!(import_error(&central, &altered).contains("signature does not match"));
+    }
+
+    #[test]
+    fn package_signed_by_another_key_is_rejected() {
+        let (_, package, central) = exported();
+        let other = SigningKey::generate(&mut OsRng);
+        trust(&central, 1, &STANDARD.encode(other.verifying_key().to_bytes()), 1).unwrap();
+        assert!(import_error(&central, &package).contains("signature does not match"));
+    }
+
+    #[test]
+    fn tally_that_does_not_match_its_ballots_is_rejected() {
+        let (station, package, central) = exported();
+        let inflated = resign(&station, &package, |report| report.positions[0].candidates[0].marks += 1);
+        assert!(import_error(&central, &inflated).contains("tallies for position 0 do not match its ballots"));
+    }
+
+    #[test]
+    fn over_voted_ballot_is_rejected() {
+        let (station, package, central) = exported();
+        let over_voted = resign(&station, &package, |report| {
+            let ballot = &mut report.ballots[0];
+            let other = if ballot.votes[0].1 == 1 { 2 } else { 1 };
+            ballot.votes.push((0, other, None, None));
+            let tally = report.positions[0].candidates.iter_mut().find(|c| c.candidate_id == other).unwrap();
+            tally.marks += 1;
+        });
+        assert!(import_error(&central, &over_voted).contains("is not marked the way a Plurality ballot is"));
+        let stored: u64 = central.query_row("SELECT COUNT(*) FROM ballots", [], |row| row.get(0)).unwrap();
+        assert_eq!(stored, 0);
+    }
+}
@@ -0,0 +1,537 @@
+// ============================================================
+// File: tally.rs
+// Purpose: Counting algorithms for the ballot methods beyond plurality.
+//
+// Responsibilities:
+// - Instant-runoff (IRV) tabulation of ranked ballots
+// - Multi-winner single transferable vote (STV) tabulation
+// - Approval and score totals
+// - Ballot question (referendum) outcomes against pass thresholds
+// - Round-by-round reports with transfers and exhausted ballots
+//
+// Tie-breaking: when several candidates share the lowest total, the one
+// with fewer votes in the most recent earlier round where they differ is
+// eliminated; if they were tied in every round, the candidate with the
+// highest ID (the one registered last) is eliminated. When several STV
+// candidates reach the quota together they are elected, and their
+// surpluses transferred, in order of votes (highest first) and then of
+// candidate ID (lowest first). Approval and score counts do not break
+// ties: every candidate sharing the top total is reported as tied.
+// Nothing random is involved, so a recount of the same ballots reproduces
+// the same result.
+//
+// STV uses the Droop quota, floor(valid / (seats + 1)) + 1, and the
+// weighted inclusive Gregory method: every ballot held by an elected
+// candidate moves on at weight * surplus / total. Weights are fixed-point
+// with five decimal places (STV_SCALE) and always truncated, so the count
+// is exact and platform independent.
+// ============================================================
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::models::Question;
+
+#[derive(Debug, Clone)]
+pub struct IrvRound {
+    pub number: usize,
+    /// Votes per continuing candidate, highest first
+    pub tallies: Vec<(i64, u64)>,
+    /// Ballots with no continuing candidate left, cumulative
+    pub exhausted: u64,
+    pub eliminated: Option<i64>,
+    /// Where the eliminated candidate's ballots went
+    pub transfers: Vec<(i64, u64)>,
+    /// Ballots of the eliminated candidate that had no further preference
+    pub newly_exhausted: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct IrvResult {
+    pub rounds: Vec<IrvRound>,
+    pub winner: Option<i64>,
+}
+
+/// First continuing candidate on a ballot, if any.
+fn top_choice(ballot: &[i64], active: &BTreeSet<i64>) -> Option<i64> {
+    ballot.iter().copied().find(|c| active.contains(c))
+}
+
+/// Pick the candidate to eliminate among those tied on the lowest total,
+/// using the tie-breaking rule described at the top of this file.
+pub fn break_tie(tied: &[i64], history: &[BTreeMap<i64, u64>]) -> i64 {
+    let mut remaining: Vec<i64> = tied.to_vec();
+    for counts in history.iter().rev() {
+        if remaining.len() == 1 {
+            break;
+        }
+        let min = remaining.iter().map(|c| counts.get(c).copied().unwrap_or(0)).min().unwrap_or(0);
+        remaining.retain(|c| counts.get(c).copied().unwrap_or(0) == min);
+    }
+    remaining.into_iter().max().expect("tie-break needs at least one candidate")
+}
+
+/// Run an instant-runoff count. `candidates` are the eligible candidate IDs
+/// for the position; preferences for anyone else are skipped.
+pub fn irv(candidates: &[i64], ballots: &[Vec<i64>]) -> IrvResult {
+    let mut active: BTreeSet<i64> = candidates.iter().copied().collect();
+    let mut rounds = Vec::new();
+    let mut history: Vec<BTreeMap<i64, u64>> = Vec::new();
+    let mut winner = None;
+
+    while !active.is_empty() {
+        let mut counts: BTreeMap<i64, u64> = active.iter().map(|c| (*c, 0)).collect();
+        let mut exhausted = 0;
+        for ballot in ballots {
+            match top_choice(ballot, &active) {
+                Some(c) => *counts.get_mut(&c).unwrap() += 1,
+                None => exhausted += 1,
+            }
+        }
+
+        let mut tallies: Vec<(i64, u64)> = counts.iter().map(|(c, v)| (*c, *v)).collect();
+        tallies.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let continuing: u64 = tallies.iter().map(|(_, v)| v).sum();
+        let mut round = IrvRound {
+            number: rounds.len() + 1,
+            tallies: tallies.clone(),
+            exhausted,
+            eliminated: None,
+            transfers: Vec::new(),
+            newly_exhausted: 0,
+        };
+
+        if continuing == 0 {
+            rounds.push(round);
+            break;
+        }
+        let (leader, leader_votes) = tallies[0];
+        if leader_votes * 2 > continuing || active.len() == 1 {
+            winner = Some(leader);
+            rounds.push(round);
+            break;
+        }
+
+        let lowest = tallies.iter().map(|(_, v)| *v).min().unwrap();
+        let tied: Vec<i64> = tallies.iter().filter(|(_, v)| *v == lowest).map(|(c, _)| *c).collect();
+        let loser = break_tie(&tied, &history);
+
+        let loser_ballots: Vec<&Vec<i64>> =
+            ballots.iter().filter(|b| top_choice(b, &active) == Some(loser)).collect();
+        active.remove(&loser);
+        let mut transfers: BTreeMap<i64, u64> = BTreeMap::new();
+        for ballot in loser_ballots {
+            match top_choice(ballot, &active) {
+                Some(next) => *transfers.entry(next).or_insert(0) += 1,
+                None => round.newly_exhausted += 1,
+            }
+        }
+        round.eliminated = Some(loser);
+        round.transfers = transfers.into_iter().collect();
+        history.push(counts);
+        rounds.push(round);
+    }
+
+    IrvResult { rounds, winner }
+}
+
+/// Fixed-point scale for STV ballot weights (five decimal places).
+pub const STV_SCALE: u64 = 100_000;
+
+#[derive(Debug, Clone)]
+pub struct StvRound {
+    pub number: usize,
+    /// Weighted votes (scaled by STV_SCALE) per continuing candidate, highest first
+    pub tallies: Vec<(i64, u64)>,
+    /// Weighted votes with no continuing candidate left, cumulative
+    pub exhausted: u64,
+    /// Candidates elected in this round
+    pub elected: Vec<i64>,
+    pub eliminated: Option<i64>,
+    /// Weighted votes moved to each continuing candidate
+    pub transfers: Vec<(i64, u64)>,
+    /// Weighted votes that exhausted during this round's transfer
+    pub newly_exhausted: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct StvResult {
+    pub seats: u32,
+    pub valid_ballots: u64,
+    pub quota: u64,
+    pub rounds: Vec<StvRound>,
+    /// Winners in the order they were elected
+    pub winners: Vec<i64>,
+}
+
+/// Run a single transferable vote count for `seats` seats.
+pub fn stv(candidates: &[i64], ballots: &[Vec<i64>], seats: u32) -> StvResult {
+    let mut active: BTreeSet<i64> = candidates.iter().copied().collect();
+    let valid: Vec<&Vec<i64>> = ballots.iter().filter(|b| top_choice(b, &active).is_some()).collect();
+    let valid_ballots = valid.len() as u64;
+    let quota = valid_ballots / (seats as u64 + 1) + 1;
+    let quota_scaled = quota * STV_SCALE;
+
+    let mut weights: Vec<u64> = vec![STV_SCALE; valid.len()];
+    let mut winners: Vec<i64> = Vec::new();
+    let mut rounds = Vec::new();
+    let mut history: Vec<BTreeMap<i64, u64>> = Vec::new();
+    if valid_ballots == 0 {
+        active.clear();
+    }
+
+    while (winners.len() as u32) < seats && !active.is_empty() {
+        let mut counts: BTreeMap<i64, u64> = active.iter().map(|c| (*c, 0)).collect();
+        let mut exhausted = 0;
+        let tops: Vec<Option<i64>> = valid.iter().map(|b| top_choice(b, &active)).collect();
+        for (top, w) in tops.iter().zip(&weights) {
+            match top {
+                Some(c) => *counts.get_mut(c).unwrap() += w,
+                None => exhausted += w,
+            }
+        }
+
+        let mut tallies: Vec<(i64, u64)> = counts.iter().map(|(c, v)| (*c, *v)).collect();
+        tallies.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut round = StvRound {
+            number: rounds.len() + 1,
+            tallies: tallies.clone(),
+            exhausted,
+            elected: Vec::new(),
+            eliminated: None,
+            transfers: Vec::new(),
+            newly_exhausted: 0,
+        };
+
+        // Just enough candidates left to fill the remaining seats.
+        if active.len() + winners.len() <= seats as usize {
+            round.elected = tallies.iter().map(|(c, _)| *c).collect();
+            winners.extend(&round.elected);
+            rounds.push(round);
+            break;
+        }
+
+        let reached: Vec<(i64, u64)> = tallies.iter().copied().filter(|(_, v)| *v >= quota_scaled).collect();
+        let mut transfers: BTreeMap<i64, u64> = BTreeMap::new();
+        if !reached.is_empty() {
+            for (c, _) in &reached {
+                active.remove(c);
+                if (winners.len() as u32) < seats {
+                    winners.push(*c);
+                    round.elected.push(*c);
+                }
+            }
+            for (c, total) in &reached {
+                let surplus = total - quota_scaled;
+                for (i, top) in tops.iter().enumerate() {
+                    if *top != Some(*c) {
+                        continue;
+                    }
+                    weights[i] = weights[i] * surplus / total;
+                    match top_choice(valid[i], &active) {
+                        Some(next) => *transfers.entry(next).or_insert(0) += weights[i],
+                        None => round.newly_exhausted += weights[i],
+                    }
+                }
+            }
+        } else {
+            let lowest = tallies.iter().map(|(_, v)| *v).min().unwrap();
+            let tied: Vec<i64> = tallies.iter().filter(|(_, v)| *v == lowest).map(|(c, _)| *c).collect();
+            let loser = break_tie(&tied, &history);
+            active.remove(&loser);
+            for (i, top) in tops.iter().enumerate() {
+                if *top != Some(loser) {
+                    continue;
+                }
+                match top_choice(valid[i], &active) {
+                    Some(next) => *transfers.entry(next).or_insert(0) += weights[i],
+                    None => round.newly_exhausted += weights[i],
+                }
+            }
+            round.eliminated = Some(loser);
+        }
+
+        round.transfers = transfers.into_iter().collect();
+        history.push(counts);
+        rounds.push(round);
+    }
+
+    StvResult { seats, valid_ballots, quota, rounds, winners }
+}
+
+#[derive(Debug, Clone)]
+pub struct TotalsResult {
+    /// Ballots that marked this position
+    pub ballots: u64,
+    /// Approvals or summed scores per candidate, highest first
+    pub totals: Vec<(i64, u64)>,
+    /// Every candidate sharing the top total (more than one means a tie)
+    pub leaders: Vec<i64>,
+}
+
+fn rank_totals(candidates: &[i64], ballots: u64, sums: BTreeMap<i64, u64>) -> TotalsResult {
+    let mut totals: Vec<(i64, u64)> = candidates.iter().map(|c| (*c, sums.get(c).copied().unwrap_or(0))).collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let top = totals.first().map(|(_, v)| *v).unwrap_or(0);
+    let leaders = if top == 0 {
+        Vec::new()
+    } else {
+        totals.iter().filter(|(_, v)| *v == top).map(|(c, _)| *c).collect()
+    };
+    TotalsResult { ballots, totals, leaders }
+}
+
+/// Count approvals: each ballot adds one to every candidate it approves.
+pub fn approval(candidates: &[i64], ballots: &[Vec<i64>]) -> TotalsResult {
+    let mut sums: BTreeMap<i64, u64> = BTreeMap::new();
+    for ballot in ballots {
+        for c in ballot.iter().collect::<BTreeSet<_>>() {
+            *sums.entry(*c).or_insert(0) += 1;
+        }
+    }
+    rank_totals(candidates, ballots.len() as u64, sums)
+}
+
+/// Sum scores: each ballot adds its score for every candidate.
+pub fn score(candidates: &[i64], ballots: &[Vec<(i64, u32)>]) -> TotalsResult {
+    let mut sums: BTreeMap<i64, u64> = BTreeMap::new();
+    for ballot in ballots {
+        for (c, s) in ballot {
+            *sums.entry(*c).or_insert(0) += *s as u64;
+        }
+    }
+    rank_totals(candidates, ballots.len() as u64, sums)
+}
+
+/// Render a scaled STV weight as a decimal, e.g. 1.33333.
+fn fmt_weight(v: u64) -> String {
+    format!("{}.{:05}", v / STV_SCALE, v % STV_SCALE)
+}
+
+fn name_of(names: &HashMap<i64, String>, id: i64) -> String {
+    names.get(&id).cloned().unwrap_or_else(|| format!("#{id}"))
+}
+
+pub fn print_irv(result: &IrvResult, names: &HashMap<i64, String>) {
+    for round in &result.rounds {
+        println!("    Round {}:", round.number);
+        for (cid, votes) in &round.tallies {
+            println!("      {} -> {} votes", name_of(names, *cid), votes);
+        }
+        println!("      Exhausted ballots: {}", round.exhausted);
+        if let Some(loser) = round.eliminated {
+            println!("      Eliminated: {}", name_of(names, loser));
+            for (to, n) in &round.transfers {
+                println!("        {} ballots transferred to {}", n, name_of(names, *to));
+            }
+            if round.newly_exhausted > 0 {
+                println!("        {} ballots exhausted", round.newly_exhausted);
+            }
+        }
+    }
+    match result.winner {
+        Some(w) => println!("    Winner: {}", name_of(names, w)),
+        None => println!("    No winner (no continuing ballots)."),
+    }
+}
+
+pub fn print_stv(result: &StvResult, names: &HashMap<i64, String>) {
+    println!(
+        "    Seats: {}, valid ballots: {}, Droop quota: {}",
+        result.seats, result.valid_ballots, result.quota
+    );
+    for round in &result.rounds {
+        println!("    Round {}:", round.number);
+        for (cid, votes) in &round.tallies {
+            println!("      {} -> {} votes", name_of(names, *cid), fmt_weight(*votes));
+        }
+        println!("      Exhausted: {}", fmt_weight(round.exhausted));
+        for cid in &round.elected {
+            println!("      Elected: {}", name_of(names, *cid));
+        }
+        if let Some(loser) = round.eliminated {
+            println!("      Eliminated: {}", name_of(names, loser));
+        }
+        for (to, v) in &round.transfers {
+            println!("        {} transferred to {}", fmt_weight(*v), name_of(names, *to));
+        }
+        if round.newly_exhausted > 0 {
+            println!("        {} exhausted", fmt_weight(round.newly_exhausted));
+        }
+    }
+    if result.winners.is_empty() {
+        println!("    No winners (no valid ballots).");
+    } else {
+        let list: Vec<String> = result.winners.iter().map(|c| name_of(names, *c)).collect();
+        println!("    Winners: {}", list.join(", "));
+    }
+}
+
+/// Print approval or score totals. `with_average` adds the mean score per
+/// ballot, which is only meaningful for score voting.
+pub fn print_totals(result: &TotalsResult, names: &HashMap<i64, String>, unit: &str, with_average: bool) {
+    println!("    Ballots: {}", result.ballots);
+    for (cid, total) in &result.totals {
+        if with_average && result.ballots > 0 {
+            let avg = *total as f64 / result.ballots as f64;
+            println!("    {} -> {} {} (average {:.2})", name_of(names, *cid), total, unit, avg);
+        } else {
+            println!("    {} -> {} {}", name_of(names, *cid), total, unit);
+        }
+    }
+    match result.leaders.as_slice() {
+        [] => println!("    No winner (no {unit} recorded)."),
+        [w] => println!("    Winner: {}", name_of(names, *w)),
+        tied => {
+            let list: Vec<String> = tied.iter().map(|c| name_of(names, *c)).collect();
+            println!("    Tie: {}", list.join(", "));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuestionResult {
+    /// Answers per option, in ballot order
+    pub tallies: Vec<(i64, u64)>,
+    /// Answers cast (abstentions excluded)
+    pub total: u64,
+    pub registered: u64,
+    pub quorum_met: bool,
+    /// Option that met the pass threshold, if any and if quorum was reached
+    pub carried: Option<i64>,
+}
+
+/// Decide a ballot question. The leading option carries when its share of
+/// the answers meets the question's threshold and enough registered voters
+/// answered to satisfy the quorum.
+pub fn question(question: &Question, tallies: &[(i64, u64)], registered: u64) -> QuestionResult {
+    let total: u64 = tallies.iter().map(|(_, v)| v).sum();
+    let quorum_met = total * 100 >= question.quorum_percent as u64 * registered;
+    let carried = tallies
+        .iter()
+        .find(|(_, v)| question.threshold.is_met(*v, total))
+        .map(|(oid, _)| *oid)
+        .filter(|_| quorum_met);
+    QuestionResult { tallies: tallies.to_vec(), total, registered, quorum_met, carried }
+}
+
+pub fn print_question(result: &QuestionResult, question: &Question) {
+    let label = |oid: i64| {
+        question.options.iter().find(|(id, _)| *id == oid).map(|(_, l)| l.clone()).unwrap_or_else(|| format!("#{oid}"))
+    };
+    for (oid, votes) in &result.tallies {
+        let pct = if result.total > 0 { *votes as f64 * 100.0 / result.total as f64 } else { 0.0 };
+        println!("    {} -> {} votes ({:.1}%)", label(*oid), votes, pct);
+    }
+    if question.quorum_percent > 0 {
+        println!(
+            "    Quorum: {} of {} registered voters answered (needs {}%) - {}",
+            result.total,
+            result.registered,
+            question.quorum_percent,
+            if result.quorum_met { "met" } else { "NOT met" }
+        );
+    }
+    match result.carried {
+        Some(oid) => println!("    Outcome: '{}' carried", label(oid)),
+        None if !result.quorum_met => println!("    Outcome: void (quorum not met)"),
+        None => println!("    Outcome: no option reached the threshold"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat(ballot: &[i64], n: usize) -> Vec<Vec<i64>> {
+        vec![ballot.to_vec(); n]
+    }
+
+    #[test]
+    fn irv_eliminates_lowest_and_transfers() {
+        let mut ballots = repeat(&[1, 2], 4);
+        ballots.extend(repeat(&[2, 3], 3));
+        ballots.extend(repeat(&[3, 2], 2));
+        ballots.extend(repeat(&[4, 3], 1));
+        let result = irv(&[1, 2, 3, 4], &ballots);
+
+        let eliminated: Vec<Option<i64>> = result.rounds.iter().map(|r| r.eliminated).collect();
+        assert_eq!(eliminated, vec![Some(4), Some(3), None]);
+        assert_eq!(result.rounds[0].tallies, vec![(1, 4), (2, 3), (3, 2), (4, 1)]);
+        assert_eq!(result.rounds[0].transfers, vec![(3, 1)]);
+        // 2 and 3 tie on 3 in round two; 3 had fewer in round one.
+        assert_eq!(result.rounds[1].tallies, vec![(1, 4), (2, 3), (3, 3)]);
+        assert_eq!(result.rounds[1].transfers, vec![(2, 2)]);
+        assert_eq!(result.rounds[1].newly_exhausted, 1);
+        assert_eq!(result.rounds[2].tallies, vec![(2, 5), (1, 4)]);
+        assert_eq!(result.rounds[2].exhausted, 1);
+        assert_eq!(result.winner, Some(2));
+    }
+
+    #[test]
+    fn irv_recount_is_identical() {
+        let mut ballots = repeat(&[1], 2);
+        ballots.extend(repeat(&[2], 2));
+        ballots.extend(repeat(&[3, 1], 1));
+        let first = irv(&[1, 2, 3], &ballots);
+        let second = irv(&[1, 2, 3], &ballots);
+        assert_eq!(first.winner, second.winner);
+        let order = |r: &IrvResult| r.rounds.iter().map(|r| r.eliminated).collect::<Vec<_>>();
+        assert_eq!(order(&first), order(&second));
+    }
+
+    #[test]
+    fn stv_transfers_surplus_at_truncated_weight() {
+        let mut ballots = repeat(&[1, 2], 4);
+        ballots.extend(repeat(&[1, 3], 3));
+        ballots.extend(repeat(&[2], 2));
+        ballots.extend(repeat(&[3], 2));
+        let result = stv(&[1, 2, 3], &ballots, 2);
+
+        // 11 valid ballots, 2 seats: floor(11 / 3) + 1 = 4.
+        assert_eq!(result.valid_ballots, 11);
+        assert_eq!(result.quota, 4);
+
+        // Candidate 1 has 7 with a quota of 4, so each of its ballots moves
+        // on at 3/7 = 0.42857 (truncated from 0.428571...).
+        let first = &result.rounds[0];
+        assert_eq!(first.elected, vec![1]);
+        assert_eq!(first.transfers, vec![(2, 4 * 42_857), (3, 3 * 42_857)]);
+
+        let second = &result.rounds[1];
+        assert_eq!(second.tallies, vec![(2, 371_428), (3, 328_571)]);
+        assert_eq!(second.eliminated, Some(3));
+        assert_eq!(second.newly_exhausted, 328_571);
+
+        assert_eq!(result.rounds[2].elected, vec![2]);
+        assert_eq!(result.winners, vec![1, 2]);
+    }
+
+    #[test]
+    fn stv_elects_simultaneous_winners_by_votes_then_id() {
+        let mut ballots = repeat(&[2], 3);
+        ballots.extend(repeat(&[1], 3));
+        ballots.extend(repeat(&[3], 1));
+        let result = stv(&[1, 2, 3], &ballots, 2);
+        assert_eq!(result.quota, 3);
+        assert_eq!(result.rounds[0].elected, vec![1, 2]);
+        assert_eq!(result.winners, vec![1, 2]);
+    }
+
+    #[test]
+    fn tie_uses_most_recent_differing_round() {
+        let history = vec![
+            BTreeMap::from([(1, 2), (2, 5), (3, 3)]),
+            BTreeMap::from([(1, 4), (2, 4), (3, 4)]),
+        ];
+        // Level in the last round, so the round before decides.
+        assert_eq!(break_tie(&[1, 2, 3], &history), 1);
+        assert_eq!(break_tie(&[2, 3], &history), 3);
+    }
+
+    #[test]
+    fn tie_level_in_every_round_eliminates_highest_id() {
+        let history = vec![BTreeMap::from([(4, 1), (7, 1)])];
+        assert_eq!(break_tie(&[4, 7], &history), 7);
+        assert_eq!(break_tie(&[4, 7], &[]), 7);
+    }
+}
@@ -0,0 +1,88 @@
+// ============================================================
+// File: token.rs
+// Purpose: Single-use voting tokens handed out at the polling station.
+//
+// Responsibilities:
+// - Issue a short printable code bound to one voter and one election
+// - Accept a code in place of the voter's PIN at login
+// - Spend the token when the ballot is cast, or let it expire
+//
+// Only a SHA-256 hash of each code is stored. A voter has at most one
+// live token per election; issuing a new one voids the old. Tokens last
+// `token.minutes` (config.rs) and can only vote in their own election.
+// ============================================================
+
+use chrono::{Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::models::VotingToken;
+use crate::vote::{normalize_code, random_code, to_hex};
+
+fn code_hash(code: &str) -> String {
+    to_hex(&Sha256::digest(normalize_code(code).as_bytes()))
+}
+
+/// Issue a token for `voter_id` in `election_id`, voiding any earlier
+/// unused one. Returns the code to print and when it expires.
+pub fn issue(conn: &Connection, voter_id: i64, election_id: i64, issued_by: i64) -> AppResult<(String, String)> {
+    let minutes = config::get(conn, "token.minutes")?;
+    let now = Utc::now();
+    let expires_at = (now + Duration::minutes(minutes)).to_rfc3339();
+    let code = random_code(8);
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE voting_tokens SET revoked_at=?1
+         WHERE voter_id=?2 AND election_id=?3 AND used_at IS NULL AND revoked_at IS NULL",
+        params![now.to_rfc3339(), voter_id, election_id],
+    )?;
+    tx.execute(
+        "INSERT INTO voting_tokens (voter_id, election_id, token_hash, issued_by, issued_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![voter_id, election_id, code_hash(&code), issued_by, now.to_rfc3339(), expires_at],
+    )?;
+    tx.commit()?;
+    Ok((code, expires_at))
+}
+
+/// The live token `code` belongs to, if it is this voter's and has not been
+/// used, voided or expired.
+pub fn find(conn: &Connection, voter_id: i64, code: &str) -> rusqlite::Result<Option<VotingToken>> {
+    conn.query_row(
+        "SELECT id, voter_id, election_id, expires_at FROM voting_tokens
+         WHERE voter_id=?1 AND token_hash=?2 AND used_at IS NULL AND revoked_at IS NULL AND expires_at > ?3",
+        params![voter_id, code_hash(code), Utc::now().to_rfc3339()],
+        |row| Ok(VotingToken { id: row.get(0)?, voter_id: row.get(1)?, election_id: row.get(2)?, expires_at: row.get(3)? }),
+    )
+    .optional()
+}
+
+/// Refuse a ballot cast under `token_id` unless the token is still live and
+/// was issued for this voter and election.
+pub fn require_live(conn: &Connection, token_id: i64, voter_id: i64, election_id: i64) -> AppResult<()> {
+    let live: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM voting_tokens WHERE id=?1 AND voter_id=?2 AND election_id=?3
+             AND used_at IS NULL AND revoked_at IS NULL AND expires_at > ?4)",
+        params![token_id, voter_id, election_id, Utc::now().to_rfc3339()],
+        |row| row.get(0),
+    )?;
+    if live {
+        Ok(())
+    } else {
+        Err(AppError::Unauthenticated("voting token has expired or is not valid for this election".to_string()))
+    }
+}
+
+/// Mark every live token of the voter for the election as used. Called in
+/// the same transaction that records the ballot.
+pub fn spend(conn: &Connection, voter_id: i64, election_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE voting_tokens SET used_at=?1
+         WHERE voter_id=?2 AND election_id=?3 AND used_at IS NULL AND revoked_at IS NULL",
+        params![Utc::now().to_rfc3339(), voter_id, election_id],
+    )?;
+    Ok(())
+}
@@ -3,24 +3,135 @@
 // Purpose: Vote-specific operations (queries and inserts)
 // ============================================================
 
-use chrono::Utc;
-use rusqlite::{params, Connection};
+use std::collections::HashSet;
 
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::audit;
+use crate::district;
+use crate::token;
+use crate::election::require_status;
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    BallotEntry, BallotRules, ElectionStatus, Position, PositionChoice, Question, QuestionAnswer, Receipt, WriteIn,
+    WriteInStatus,
+};
+
+/// Whether the voter has voted in the election, here or at an offline
+/// polling station whose results were imported (see station.rs).
 pub fn has_voted(conn: &Connection, voter_id: i64, election_id: i64) -> bool {
     conn.query_row(
-        "SELECT EXISTS(SELECT 1 FROM votes WHERE election_id=?1 AND voter_id=?2)",
+        "SELECT EXISTS(SELECT 1 FROM participation WHERE election_id=?1 AND voter_id=?2)
+             OR EXISTS(SELECT 1 FROM station_participation WHERE election_id=?1 AND voter_id=?2)",
         params![election_id, voter_id],
         |row| row.get(0),
     )
     .unwrap_or(false)
 }
 
+/// Pick an unused random row ID for `table`. Ballot-box rows use these
+/// instead of sequential IDs so their order cannot be matched against the
+/// order voters were marked as having voted.
+pub fn random_id(conn: &Connection, table: &str) -> rusqlite::Result<i64> {
+    let mut rng = rand::thread_rng();
+    loop {
+        let id: i64 = rng.gen_range(1..=i32::MAX as i64);
+        let taken: bool = conn.query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE id=?1)"),
+            params![id],
+            |row| row.get(0),
+        )?;
+        if !taken {
+            return Ok(id);
+        }
+    }
+}
+
 pub fn list_elections(conn: &Connection) -> rusqlite::Result<Vec<(i64, String)>> {
     let mut stmt = conn.prepare("SELECT id, name FROM elections ORDER BY id ASC")?;
     let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
     Ok(rows.filter_map(|r| r.ok()).collect())
 }
 
+pub fn list_positions(conn: &Connection, election_id: i64) -> rusqlite::Result<Vec<Position>> {
+    let mut stmt = conn.prepare(
+        "SELECT idx, title, method, seats, min_selections, max_selections, max_score, allow_write_in FROM positions WHERE election_id=?1 ORDER BY idx ASC",
+    )?;
+    let rows = stmt.query_map(params![election_id], |row| {
+        Ok(Position {
+            idx: row.get(0)?,
+            title: row.get(1)?,
+            rules: BallotRules {
+                method: row.get(2)?,
+                seats: row.get(3)?,
+                min_selections: row.get(4)?,
+                max_selections: row.get(5)?,
+                max_score: row.get(6)?,
+                allow_write_in: row.get(7)?,
+            },
+        })
+    })?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+pub fn list_questions(conn: &Connection, election_id: i64) -> rusqlite::Result<Vec<Question>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, idx, text, threshold, quorum_percent FROM questions WHERE election_id=?1 ORDER BY idx ASC",
+    )?;
+    let rows = stmt.query_map(params![election_id], |row| {
+        Ok(Question {
+            id: row.get(0)?,
+            idx: row.get(1)?,
+            text: row.get(2)?,
+            threshold: row.get(3)?,
+            quorum_percent: row.get(4)?,
+            options: Vec::new(),
+        })
+    })?;
+    let mut questions: Vec<Question> = rows.filter_map(|r| r.ok()).collect();
+
+    let mut opt_stmt = conn.prepare("SELECT id, label FROM question_options WHERE question_id=?1 ORDER BY idx ASC")?;
+    for q in &mut questions {
+        let opts = opt_stmt.query_map(params![q.id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        q.options = opts.filter_map(|r| r.ok()).collect();
+    }
+    Ok(questions)
+}
+
+/// Number of answers each option of a question received.
+pub fn question_tallies(conn: &Connection, question_id: i64) -> rusqlite::Result<Vec<(i64, u64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT o.id, COUNT(a.id) FROM question_options o LEFT JOIN answers a ON a.option_id = o.id
+         WHERE o.question_id=?1 GROUP BY o.id ORDER BY o.idx",
+    )?;
+    let rows = stmt.query_map(params![question_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// Write-ins for an election, optionally only those still pending.
+pub fn list_write_ins(conn: &Connection, election_id: i64, pending_only: bool) -> rusqlite::Result<Vec<WriteIn>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, position_idx, name, status, candidate_id FROM write_ins
+         WHERE election_id=?1 AND (?2 = 0 OR status = 'Pending') ORDER BY position_idx, name COLLATE NOCASE, id",
+    )?;
+    let rows = stmt.query_map(params![election_id, pending_only], |row| {
+        Ok(WriteIn {
+            id: row.get(0)?,
+            position_idx: row.get(1)?,
+            name: row.get(2)?,
+            status: row.get(3)?,
+            candidate_id: row.get(4)?,
+        })
+    })?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+pub fn registered_voter_count(conn: &Connection) -> rusqlite::Result<u64> {
+    conn.query_row("SELECT COUNT(*) FROM voters", [], |row| row.get(0))
+}
+
 pub fn list_candidates(
     conn: &Connection,
     election_id: i64,
@@ -34,19 +145,380 @@ pub fn list_candidates(
     Ok(rows.filter_map(|r| r.ok()).collect())
 }
 
+/// Store one position's selections for a ballot. A plurality choice is a
+/// single row; a ranking is one row per ranked candidate with its `rank`
+/// (1 = first preference); approvals are one row per approved candidate and
+/// scores one row per candidate with its `score`. A write-in goes to the
+/// adjudication queue instead. Abstentions store nothing.
 pub fn record_vote(
     conn: &Connection,
+    ballot_id: i64,
     election_id: i64,
-    voter_id: i64,
-    position_idx: i32,
-    candidate_id: i64,
+    entry: &BallotEntry,
 ) -> rusqlite::Result<()> {
-    let now = Utc::now().to_rfc3339();
-    conn.execute(
-        "INSERT INTO votes (election_id, voter_id, position_idx, candidate_id, cast_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![election_id, voter_id, position_idx, candidate_id, now],
+    let marks: Vec<(i64, Option<i32>, Option<u32>)> = match &entry.choice {
+        PositionChoice::Abstain => Vec::new(),
+        PositionChoice::Candidate(cid) => vec![(*cid, None, None)],
+        PositionChoice::Ranked(ranking) => ranking
+            .iter()
+            .enumerate()
+            .map(|(i, cid)| (*cid, Some(i as i32 + 1), None))
+            .collect(),
+        PositionChoice::Approval(approved) => approved.iter().map(|cid| (*cid, None, None)).collect(),
+        PositionChoice::Score(scores) => scores.iter().map(|(cid, score)| (*cid, None, Some(*score))).collect(),
+        PositionChoice::WriteIn(name) => {
+            conn.execute(
+                "INSERT INTO write_ins (id, ballot_id, election_id, position_idx, name, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![random_id(conn, "write_ins")?, ballot_id, election_id, entry.position_idx, name, WriteInStatus::Pending],
+            )?;
+            Vec::new()
+        }
+    };
+    for (candidate_id, rank, score) in marks {
+        conn.execute(
+            "INSERT INTO votes (id, ballot_id, election_id, position_idx, candidate_id, rank, score) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![random_id(conn, "votes")?, ballot_id, election_id, entry.position_idx, candidate_id, rank, score],
+        )?;
+    }
+    Ok(())
+}
+
+/// Load every ranked ballot for a position as a list of candidate IDs in
+/// preference order. Ballots that ranked nobody for this position are omitted.
+pub fn ranked_ballots(conn: &Connection, election_id: i64, position_idx: i32) -> rusqlite::Result<Vec<Vec<i64>>> {
+    let mut stmt = conn.prepare(
+        "SELECT ballot_id, candidate_id FROM votes WHERE election_id=?1 AND position_idx=?2 ORDER BY ballot_id, rank",
     )?;
+    let rows = stmt.query_map(params![election_id, position_idx], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut ballots: Vec<Vec<i64>> = Vec::new();
+    let mut current: Option<i64> = None;
+    for (ballot_id, candidate_id) in rows.filter_map(|r| r.ok()) {
+        if current != Some(ballot_id) {
+            ballots.push(Vec::new());
+            current = Some(ballot_id);
+        }
+        ballots.last_mut().unwrap().push(candidate_id);
+    }
+    Ok(ballots)
+}
+
+/// Load every score ballot for a position as (candidate ID, score) pairs.
+pub fn scored_ballots(conn: &Connection, election_id: i64, position_idx: i32) -> rusqlite::Result<Vec<Vec<(i64, u32)>>> {
+    let mut stmt = conn.prepare(
+        "SELECT ballot_id, candidate_id, COALESCE(score, 0) FROM votes WHERE election_id=?1 AND position_idx=?2 ORDER BY ballot_id, candidate_id",
+    )?;
+    let rows = stmt.query_map(params![election_id, position_idx], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, u32>(2)?))
+    })?;
+
+    let mut ballots: Vec<Vec<(i64, u32)>> = Vec::new();
+    let mut current: Option<i64> = None;
+    for (ballot_id, candidate_id, score) in rows.filter_map(|r| r.ok()) {
+        if current != Some(ballot_id) {
+            ballots.push(Vec::new());
+            current = Some(ballot_id);
+        }
+        ballots.last_mut().unwrap().push((candidate_id, score));
+    }
+    Ok(ballots)
+}
+
+/// Check that a position's selection respects its ballot rules. The voter
+/// flow enforces the same limits while prompting; this is the last word.
+pub fn validate_choice(rules: &BallotRules, choice: &PositionChoice) -> AppResult<()> {
+    let marked = match choice {
+        PositionChoice::Ranked(ranking) => {
+            if ranking.is_empty() {
+                return Err(AppError::Invalid("a ranking must list at least one candidate".to_string()));
+            }
+            if let Some(cid) = first_repeat(ranking.iter().copied()) {
+                return Err(AppError::Invalid(format!("candidate {cid} is ranked more than once")));
+            }
+            ranking.len() as u32
+        }
+        PositionChoice::Approval(approved) => {
+            if let Some(cid) = first_repeat(approved.iter().copied()) {
+                return Err(AppError::Invalid(format!("candidate {cid} is approved more than once")));
+            }
+            approved.len() as u32
+        }
+        PositionChoice::Score(scores) => {
+            if let Some(cid) = first_repeat(scores.iter().map(|(cid, _)| *cid)) {
+                return Err(AppError::Invalid(format!("candidate {cid} is scored more than once")));
+            }
+            if let Some((cid, score)) = scores.iter().find(|(_, s)| *s > rules.max_score) {
+                return Err(AppError::Invalid(format!(
+                    "score {score} for candidate {cid} is above the maximum of {}",
+                    rules.max_score
+                )));
+            }
+            scores.iter().filter(|(_, s)| *s > 0).count() as u32
+        }
+        PositionChoice::WriteIn(name) => {
+            if !rules.allow_write_in {
+                return Err(AppError::Invalid("this position does not accept write-ins".to_string()));
+            }
+            if name.trim().is_empty() {
+                return Err(AppError::Invalid("a write-in needs a name".to_string()));
+            }
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+    if marked < rules.min_selections {
+        return Err(AppError::Invalid(format!("at least {} candidates must be marked", rules.min_selections)));
+    }
+    if rules.max_selections > 0 && marked > rules.max_selections {
+        return Err(AppError::Invalid(format!("at most {} candidates may be marked", rules.max_selections)));
+    }
     Ok(())
 }
 
+/// The first candidate ID that appears more than once, if any.
+fn first_repeat(mut ids: impl Iterator<Item = i64>) -> Option<i64> {
+    let mut seen = HashSet::new();
+    ids.find(|cid| !seen.insert(*cid))
+}
 
+/// Characters used in printed codes; no 0/O or 1/I to avoid misreading.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Random code of `len` characters from CODE_ALPHABET in groups of four,
+/// e.g. `7KQ2-M9XD`. Used for tracking codes and voter numbers.
+pub fn random_code(len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    let chars: Vec<char> = (0..len)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    chars.chunks(4).map(|c| c.iter().collect::<String>()).collect::<Vec<_>>().join("-")
+}
+
+/// A random tracking code such as `K7QM-3XRA-PW9D`.
+fn generate_tracking_code() -> String {
+    random_code(12)
+}
+
+/// Accept codes typed in any case, with or without dashes or spaces.
+pub fn normalize_code(input: &str) -> String {
+    let clean: Vec<char> = input
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    clean.chunks(4).map(|c| c.iter().collect::<String>()).collect::<Vec<_>>().join("-")
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// SHA-256 over the tracking code and a canonical listing of everything the
+/// voter marked. The code acts as a per-ballot salt, so identical ballots get
+/// different digests and the digest cannot be reversed by trying every
+/// possible ballot. Votes added later by write-in adjudication are left out
+/// so the digest stays what the voter was given.
+pub fn ballot_digest(conn: &Connection, ballot_id: i64, tracking_code: &str) -> rusqlite::Result<String> {
+    let election_id: i64 = conn.query_row("SELECT election_id FROM ballots WHERE id=?1", params![ballot_id], |row| row.get(0))?;
+    let mut canonical = format!("{tracking_code}|{election_id}");
+
+    let mut stmt = conn.prepare(
+        "SELECT position_idx, candidate_id, rank, score FROM votes WHERE ballot_id=?1 AND write_in_id IS NULL
+         ORDER BY position_idx, rank, candidate_id",
+    )?;
+    let votes = stmt.query_map(params![ballot_id], |row| {
+        Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?, row.get::<_, Option<i32>>(2)?, row.get::<_, Option<u32>>(3)?))
+    })?;
+    for v in votes {
+        let (pidx, cid, rank, score) = v?;
+        canonical.push_str(&format!("|v:{pidx}:{cid}:{}:{}", rank.unwrap_or(0), score.map_or(-1, |s| s as i64)));
+    }
+
+    let mut stmt = conn.prepare("SELECT question_id, option_id FROM answers WHERE ballot_id=?1 ORDER BY question_id")?;
+    let answers = stmt.query_map(params![ballot_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+    for a in answers {
+        let (qid, oid) = a?;
+        canonical.push_str(&format!("|a:{qid}:{oid}"));
+    }
+
+    let mut stmt = conn.prepare("SELECT position_idx, name FROM write_ins WHERE ballot_id=?1 ORDER BY position_idx")?;
+    let write_ins = stmt.query_map(params![ballot_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?;
+    for w in write_ins {
+        let (pidx, name) = w?;
+        canonical.push_str(&format!("|w:{pidx}:{}", name.len()));
+        canonical.push_str(&name);
+    }
+
+    Ok(to_hex(&Sha256::digest(canonical.as_bytes())))
+}
+
+/// Look up a receipt by tracking code and re-derive its digest from the
+/// stored ballot. Returns the receipt and whether the contents still match.
+pub fn verify_receipt(conn: &Connection, code: &str) -> AppResult<(Receipt, bool)> {
+    let code = normalize_code(code);
+    let (ballot_id, election_id, content_hash): (i64, i64, String) = conn
+        .query_row(
+            "SELECT id, election_id, content_hash FROM ballots WHERE tracking_code=?1",
+            params![code],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("ballot with tracking code {code}")))?;
+    let intact = ballot_digest(conn, ballot_id, &code)? == content_hash;
+    Ok((Receipt { election_id, tracking_code: code, content_hash }, intact))
+}
+
+/// The public list of ballots for an election: tracking codes and digests
+/// only, sorted by code so the order carries no information.
+pub fn published_ballots(conn: &Connection, election_id: i64) -> rusqlite::Result<Vec<Receipt>> {
+    let mut stmt = conn.prepare(
+        "SELECT tracking_code, content_hash FROM ballots
+         WHERE election_id=?1 AND tracking_code IS NOT NULL ORDER BY tracking_code",
+    )?;
+    let rows = stmt.query_map(params![election_id], |row| {
+        Ok(Receipt { election_id, tracking_code: row.get(0)?, content_hash: row.get(1)? })
+    })?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// A ballot holds at most one entry per position and one answer per
+/// question; a repeat would count the voter twice in the same race.
+fn reject_repeats(entries: &[BallotEntry], answers: &[QuestionAnswer]) -> AppResult<()> {
+    let mut positions = HashSet::new();
+    if let Some(entry) = entries.iter().find(|e| !positions.insert(e.position_idx)) {
+        return Err(AppError::Invalid(format!("position {} is marked more than once", entry.position_idx)));
+    }
+    let mut questions = HashSet::new();
+    if let Some(answer) = answers.iter().find(|a| !questions.insert(a.question_id)) {
+        return Err(AppError::Invalid(format!("question #{} is answered more than once", answer.question_id)));
+    }
+    Ok(())
+}
+
+/// Check a whole ballot before any of it is stored: each position and
+/// question at most once, only `positions` on this voter's ballot, choices
+/// within the position's rules that mark only candidates standing for it,
+/// and answers from the question's own options. Ballots imported from
+/// offline stations go through the same checks (see station.rs).
+pub fn check_ballot(
+    positions: &[Position],
+    candidates: &[(i64, String, String, i32)],
+    questions: &[Question],
+    entries: &[BallotEntry],
+    answers: &[QuestionAnswer],
+) -> AppResult<()> {
+    reject_repeats(entries, answers)?;
+    for entry in entries {
+        let position = positions.iter().find(|p| p.idx == entry.position_idx).ok_or_else(|| {
+            AppError::Invalid(format!("position {} is not on this voter's ballot", entry.position_idx))
+        })?;
+        validate_choice(&position.rules, &entry.choice)?;
+        // Every candidate marked must be standing for this position in this election.
+        let marked: Vec<i64> = match &entry.choice {
+            PositionChoice::Candidate(cid) => vec![*cid],
+            PositionChoice::Ranked(ids) | PositionChoice::Approval(ids) => ids.clone(),
+            PositionChoice::Score(scores) => scores.iter().map(|(cid, _)| *cid).collect(),
+            PositionChoice::Abstain | PositionChoice::WriteIn(_) => Vec::new(),
+        };
+        for cid in marked {
+            if !candidates.iter().any(|(id, _, _, pidx)| *id == cid && *pidx == entry.position_idx) {
+                return Err(AppError::Invalid(format!(
+                    "candidate {cid} is not standing for position {}",
+                    entry.position_idx
+                )));
+            }
+        }
+    }
+
+    for answer in answers {
+        let Some(option_id) = answer.option_id else { continue };
+        let valid = questions
+            .iter()
+            .find(|q| q.id == answer.question_id)
+            .is_some_and(|q| q.options.iter().any(|(oid, _)| *oid == option_id));
+        if !valid {
+            return Err(AppError::Invalid(format!(
+                "option {option_id} does not belong to question #{}",
+                answer.question_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Commit a complete ballot (one entry per position, one answer per ballot
+/// question) in a single transaction. Either every selection is stored or
+/// none is. The voter is recorded in `participation`; the ballot goes into
+/// the anonymous box with a random ID and no link back to them, and a
+/// tracking receipt bound to its contents is returned.
+pub fn cast_ballot(
+    conn: &Connection,
+    election_id: i64,
+    voter_id: i64,
+    token_id: Option<i64>,
+    entries: &[BallotEntry],
+    answers: &[QuestionAnswer],
+) -> AppResult<Receipt> {
+    let tx = conn.unchecked_transaction()?;
+
+    // Re-check inside the transaction so a status change or a second session
+    // between review and confirmation cannot slip a ballot through.
+    require_status(&tx, election_id, &[ElectionStatus::Open], "vote")?;
+    if has_voted(&tx, voter_id, election_id) {
+        return Err(AppError::AlreadyVoted { election_id });
+    }
+    if let Some(token_id) = token_id {
+        token::require_live(&tx, token_id, voter_id, election_id)?;
+    }
+    // Only positions contested in the voter's district may be marked.
+    let positions = district::ballot_positions(&tx, election_id, voter_id)?;
+    let candidates = list_candidates(&tx, election_id)?;
+    let questions = list_questions(&tx, election_id)?;
+    check_ballot(&positions, &candidates, &questions, entries, answers)?;
+
+    // Where it was cast, for local breakdowns and turnout. The ballot gets the
+    // same placement; like its ID, nothing ties it to the voter.
+    let (district_id, station_id) = district::voter_placement(&tx, voter_id)?;
+    tx.execute(
+        "INSERT INTO participation (election_id, voter_id, district_id) VALUES (?1, ?2, ?3)",
+        params![election_id, voter_id, district_id],
+    )?;
+    token::spend(&tx, voter_id, election_id)?;
+    // Says who voted, never which ballot is theirs: same as participation.
+    audit::record(&tx, &format!("voter #{voter_id}"), "vote_cast", &format!("election #{election_id}"))?;
+    let ballot_id = random_id(&tx, "ballots")?;
+    tx.execute(
+        "INSERT INTO ballots (id, election_id, district_id, station_id) VALUES (?1, ?2, ?3, ?4)",
+        params![ballot_id, election_id, district_id, station_id],
+    )?;
+
+    for entry in entries {
+        record_vote(&tx, ballot_id, election_id, entry)?;
+    }
+    for answer in answers {
+        let Some(option_id) = answer.option_id else { continue };
+        tx.execute(
+            "INSERT INTO answers (id, ballot_id, election_id, question_id, option_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![random_id(&tx, "answers")?, ballot_id, election_id, answer.question_id, option_id],
+        )?;
+    }
+
+    let tracking_code = loop {
+        let code = generate_tracking_code();
+        let taken: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM ballots WHERE tracking_code=?1)", params![code], |row| row.get(0))?;
+        if !taken {
+            break code;
+        }
+    };
+    let content_hash = ballot_digest(&tx, ballot_id, &tracking_code)?;
+    tx.execute(
+        "UPDATE ballots SET tracking_code=?1, content_hash=?2 WHERE id=?3",
+        params![tracking_code, content_hash, ballot_id],
+    )?;
+
+    tx.commit()?;
+    Ok(Receipt { election_id, tracking_code, content_hash })
+}
@@ -5,62 +5,202 @@
 
 use rusqlite::{params, Connection};
 
-use crate::auth::verify_password;
-use crate::vote::{has_voted, list_candidates, list_elections, record_vote};
+use crate::audit;
+use crate::auth::{change_voter_pin, upgraded_hash, verify_password};
+use crate::error::AppError;
+use crate::district::ballot_positions;
+use crate::election::require_status;
+use crate::lockout;
+use crate::models::{BallotEntry, BallotMethod, BallotRules, ElectionStatus, PositionChoice, QuestionAnswer, VotingToken};
+use crate::token;
+use crate::vote::{
+    cast_ballot, normalize_code, random_code, has_voted, list_candidates, list_elections, list_questions, validate_choice,
+};
 
 use crate::read_input; // from main.rs
 
-pub fn voter_login(conn: &Connection) -> Option<(i64, String)> {
+/// A fresh, unused voter number such as `7KQ2-M9XD`.
+pub fn new_voter_number(conn: &Connection) -> rusqlite::Result<String> {
+    loop {
+        let number = random_code(8);
+        let taken: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM voters WHERE voter_number=?1)", params![number], |row| row.get(0))?;
+        if !taken {
+            return Ok(number);
+        }
+    }
+}
+
+/// Log a voter in with their PIN, or with a voting token from the polling
+/// station. A token login comes back with the token, which limits the
+/// session to that token's election.
+pub fn voter_login(conn: &Connection) -> Option<(i64, String, Option<VotingToken>)> {
     println!("\n🔐 Voter Login");
-    let fullname = read_input("Full name: ");
-    let pin = read_input("PIN: ");
+    let voter_number = normalize_code(&read_input("Voter number: "));
+    let pin = read_input("PIN or voting token: ");
+
+    let account_key = lockout::account_key("voter", &voter_number);
+    let keys = [account_key.clone(), lockout::terminal_key()];
+    if let Err(e) = lockout::check(conn, &keys) {
+        println!("⛔ {}", e);
+        return None;
+    }
+    let fail = || {
+        if let Err(e) = lockout::record_failure(conn, &keys) {
+            println!("⚠️  Could not record failed login: {}", e);
+        }
+    };
 
-    let result: rusqlite::Result<(i64, String)> = conn.query_row(
-        "SELECT id, pinhash FROM voters WHERE fullname=?1",
-        params![fullname],
-        |row| Ok((row.get(0)?, row.get(1)?)),
+    let result: rusqlite::Result<(i64, String, String, bool)> = conn.query_row(
+        "SELECT id, fullname, pinhash, pin_must_change FROM voters WHERE voter_number=?1",
+        params![voter_number],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
     );
 
+    // Failures to write the audit entry are reported but do not block login.
+    let log = |actor: &str, action: &str, detail: &str| {
+        if let Err(e) = audit::record(conn, actor, action, detail) {
+            println!("⚠️  Could not write audit log: {}", e);
+        }
+    };
     match result {
-        Ok((voter_id, pinhash)) => {
+        Ok((voter_id, fullname, pinhash, must_change)) => {
+            // Codes are cheap to look up, so try the entry as a token before the PIN.
+            let voting_token = match token::find(conn, voter_id, &pin) {
+                Ok(t) => t,
+                Err(e) => {
+                    println!("❌ Error checking voting token: {}", e);
+                    return None;
+                }
+            };
+            if let Some(voting_token) = voting_token {
+                log(&format!("voter #{}", voter_id), "voter_login", &format!("voting token for election #{}", voting_token.election_id));
+                if let Err(e) = lockout::record_success(conn, &account_key) {
+                    println!("⚠️  Could not reset failed logins: {}", e);
+                }
+                // A token does not replace the PIN, so a reset PIN must still be changed.
+                if must_change {
+                    println!("🔑 Your PIN was reset. Please enter the temporary PIN and choose a new one before continuing.");
+                    if !change_pin_flow(conn, voter_id, None) {
+                        return None;
+                    }
+                }
+                println!("✅ Welcome, {}! Your token is valid for election #{} until {}.", fullname, voting_token.election_id, voting_token.expires_at);
+                return Some((voter_id, fullname, Some(voting_token)));
+            }
             if verify_password(&pinhash, &pin) {
+                log(&format!("voter #{}", voter_id), "voter_login", "");
+                if let Err(e) = lockout::record_success(conn, &account_key) {
+                    println!("⚠️  Could not reset failed logins: {}", e);
+                }
+                // Re-hash with the current Argon2 settings if they were strengthened.
+                match upgraded_hash(conn, &pinhash, &pin) {
+                    Ok(Some(new_hash)) => {
+                        if let Err(e) = conn.execute("UPDATE voters SET pinhash=?1 WHERE id=?2", params![new_hash, voter_id]) {
+                            println!("⚠️  Could not upgrade stored PIN hash: {}", e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => println!("⚠️  Could not upgrade stored PIN hash: {}", e),
+                }
+                if must_change {
+                    println!("🔑 Your PIN was reset. Please choose a new one before continuing.");
+                    if !change_pin_flow(conn, voter_id, Some(&pin)) {
+                        return None;
+                    }
+                }
                 println!("✅ Welcome, {}!", fullname);
-                Some((voter_id, fullname))
+                Some((voter_id, fullname, None))
             } else {
-                println!("❌ Incorrect PIN.");
+                log(&format!("voter #{}", voter_id), "voter_login_failed", "");
+                fail();
+                println!("❌ Incorrect PIN or voting token.");
                 None
             }
         }
         Err(_) => {
-            println!("⚠️  No voter found with that name.");
+            log("unknown voter", "voter_login_failed", "");
+            fail();
+            println!("⚠️  No voter found with that number.");
             None
         }
     }
 }
 
-pub fn voter_portal(conn: &Connection, voter_id: i64, voter_name: &str) {
+pub fn voter_portal(conn: &Connection, voter_id: i64, voter_name: &str, voting_token: Option<&VotingToken>) {
     loop {
         println!("\n👤 Voter Portal - {}", voter_name);
         println!("1. Vote");
-        println!("2. Back to Main Menu");
-        let choice = read_input("Select an option (1-2): ");
+        println!("2. Change PIN");
+        println!("3. Back to Main Menu");
+        let choice = read_input("Select an option (1-3): ");
 
         match choice.as_str() {
             "1" => {
-                voter_vote_flow(conn, voter_id);
+                voter_vote_flow(conn, voter_id, voting_token);
             }
-            "2" => break,
+            "2" => {
+                change_pin_flow(conn, voter_id, None);
+            }
+            "3" => break,
             "" => {
-                println!("⚠️  Please enter a valid option (1-2).");
+                println!("⚠️  Please enter a valid option (1-3).");
                 continue;
             }
-            _ => println!("❌ Invalid option. Please select 1-2."),
+            _ => println!("❌ Invalid option. Please select 1-3."),
         }
         read_input("\nPress Enter to continue...");
     }
 }
 
-pub fn voter_vote_flow(conn: &Connection, voter_id: i64) {
+/// Prompt for a new PIN (twice) and store it. `current` is the PIN just
+/// used to log in, when there is one; otherwise the voter is asked for it.
+/// Returns whether the PIN was changed.
+fn change_pin_flow(conn: &Connection, voter_id: i64, current: Option<&str>) -> bool {
+    let old_pin = match current {
+        Some(pin) => pin.to_string(),
+        None => read_input("Current PIN: "),
+    };
+    let keys = [lockout::account_key("voter", &voter_number_of(conn, voter_id)), lockout::terminal_key()];
+    loop {
+        let new_pin = read_input("New PIN (blank to cancel): ");
+        if new_pin.is_empty() {
+            println!("PIN not changed.");
+            return false;
+        }
+        if read_input("Repeat new PIN: ") != new_pin {
+            println!("❌ The PINs did not match.");
+            continue;
+        }
+        // Guessing the current PIN here counts against the same lockout as logging in.
+        if let Err(e) = lockout::check(conn, &keys) {
+            println!("⛔ {}", e);
+            return false;
+        }
+        match change_voter_pin(conn, voter_id, &old_pin, &new_pin) {
+            Ok(_) => {
+                println!("✅ PIN changed.");
+                return true;
+            }
+            Err(AppError::Unauthenticated(e)) => {
+                if let Err(e) = lockout::record_failure(conn, &keys) {
+                    println!("⚠️  Could not record failed attempt: {}", e);
+                }
+                println!("❌ {}", e);
+                return false;
+            }
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+fn voter_number_of(conn: &Connection, voter_id: i64) -> String {
+    conn.query_row("SELECT voter_number FROM voters WHERE id=?1", params![voter_id], |row| row.get(0))
+        .unwrap_or_default()
+}
+
+/// With a voting token, only the token's election is offered.
+pub fn voter_vote_flow(conn: &Connection, voter_id: i64, voting_token: Option<&VotingToken>) {
     loop {
         println!("\n🗳️  Voting Menu");
         println!("1. Select an election");
@@ -70,7 +210,10 @@ pub fn voter_vote_flow(conn: &Connection, voter_id: i64) {
         match choice.as_str() {
             "1" => {
                 // List elections
-                let elections = match list_elections(conn) { Ok(v) => v, Err(e) => { println!("Error: {}", e); return; } };
+                let mut elections = match list_elections(conn) { Ok(v) => v, Err(e) => { println!("Error: {}", e); return; } };
+                if let Some(t) = voting_token {
+                    elections.retain(|(id, _)| *id == t.election_id);
+                }
                 if elections.is_empty() { println!("No elections available."); return; }
                 println!("\nAvailable Elections:");
                 for (id, name) in &elections { println!(" - {}: {}", id, name); }
@@ -82,33 +225,36 @@ pub fn voter_vote_flow(conn: &Connection, voter_id: i64) {
                 if election_name.is_none() { println!("❌ Election not found."); continue; }
                 let election_name = election_name.unwrap();
 
+                // Only open elections accept ballots
+                if let Err(e) = require_status(conn, election_id, &[ElectionStatus::Open], "vote") {
+                    println!("❌ Voting refused: {}", e);
+                    continue;
+                }
+
                 // Double-vote check
                 if has_voted(conn, voter_id, election_id) {
                     println!("This person has already voted for the '{}', a voter can only vote once", election_name);
                     return;
                 }
 
-                // List candidates for the election
-                let candidates = match list_candidates(conn, election_id) { Ok(v) => v, Err(e) => { println!("Error: {}", e); return; } };
-                if candidates.is_empty() { println!("No candidates for this election."); return; }
-                println!("\nCandidates:");
-                for (cid, cname, party, _) in &candidates { println!(" - {}: {} ({})", cid, cname, party); }
-                let cand_input = read_input("Enter candidate ID: ");
-                let candidate_id = match cand_input.parse::<i64>() { Ok(v) => v, Err(_) => { println!("❌ Invalid candidate ID"); continue; } };
-
-                let selected = candidates.iter().find(|(cid, _, _, _)| *cid == candidate_id).cloned();
-                if selected.is_none() { println!("❌ Candidate not found."); continue; }
-                let (candidate_id, candidate_name, _party, position_idx) = selected.unwrap();
+                // Walk the voter through every position in order
+                let ballot = match collect_ballot(conn, election_id, voter_id) { Some(v) => v, None => continue };
 
-                // Confirm
-                println!("You are selecting '{}' for the '{}', are you sure?", candidate_name, election_name);
-                let confirm = read_input("Type 'Yes' to confirm, or 'No' to cancel: ");
+                // Review the full ballot before committing it
+                println!("\n📝 Review your ballot for '{}':", election_name);
+                for (title, label) in &ballot.review {
+                    println!(" - {}: {}", title, label);
+                }
+                let confirm = read_input("Type 'Yes' to cast this ballot, or 'No' to cancel: ");
                 if confirm.eq_ignore_ascii_case("Yes") {
-                    if let Err(e) = record_vote(conn, election_id, voter_id, position_idx, candidate_id) {
-                        println!("❌ Error recording vote: {}", e);
-                        return;
-                    }
+                    let receipt = match cast_ballot(conn, election_id, voter_id, voting_token.map(|t| t.id), &ballot.entries, &ballot.answers) {
+                        Ok(r) => r,
+                        Err(e) => { println!("❌ Error recording vote: {}", e); return; }
+                    };
                     println!("Thank you for your Vote!");
+                    println!("\n🧾 Your ballot tracking code: {}", receipt.tracking_code);
+                    println!("   Fingerprint: {}", receipt.fingerprint());
+                    println!("   Keep these to check your ballot was counted: rusttrust verify-receipt {}", receipt.tracking_code);
                     std::process::exit(0);
                 } else {
                     // Go back to previous menu
@@ -125,3 +271,205 @@ pub fn voter_vote_flow(conn: &Connection, voter_id: i64) {
     }
 }
 
+
+type CandidateRow = (i64, String, String, i32);
+
+/// A ballot being filled in, plus the `(title, selection)` lines shown on
+/// the review screen.
+struct DraftBallot {
+    entries: Vec<BallotEntry>,
+    answers: Vec<QuestionAnswer>,
+    review: Vec<(String, String)>,
+}
+
+/// Prompt for a selection (or abstention) on every position and ballot
+/// question of the election contested in the voter's district. Returns
+/// `None` if the voter backs out or the election cannot be loaded.
+fn collect_ballot(conn: &Connection, election_id: i64, voter_id: i64) -> Option<DraftBallot> {
+    let positions = match ballot_positions(conn, election_id, voter_id) { Ok(v) => v, Err(e) => { println!("Error: {}", e); return None; } };
+    let questions = match list_questions(conn, election_id) { Ok(v) => v, Err(e) => { println!("Error: {}", e); return None; } };
+    if positions.is_empty() && questions.is_empty() { println!("Nothing to vote on in this election."); return None; }
+    let candidates = match list_candidates(conn, election_id) { Ok(v) => v, Err(e) => { println!("Error: {}", e); return None; } };
+
+    let mut ballot = DraftBallot { entries: Vec::new(), answers: Vec::new(), review: Vec::new() };
+    for (n, position) in positions.iter().enumerate() {
+        println!("\n📌 Position {} of {}: {}", n + 1, positions.len(), position.title);
+        let options: Vec<&CandidateRow> = candidates.iter().filter(|(_, _, _, pidx)| *pidx == position.idx).collect();
+        let (choice, label) = if options.is_empty() && position.rules.allow_write_in {
+            println!("No candidates are standing for this position; you may write one in.");
+            println!(" - 0: Abstain");
+            prompt_single(&options, true)?
+        } else if options.is_empty() {
+            println!("No candidates for this position; it will be recorded as an abstention.");
+            (PositionChoice::Abstain, "Abstain".to_string())
+        } else {
+            for (cid, cname, party, _) in &options { println!(" - {}: {} ({})", cid, cname, party); }
+            println!(" - 0: Abstain");
+            match position.rules.method {
+                BallotMethod::Plurality => prompt_single(&options, position.rules.allow_write_in)?,
+                BallotMethod::RankedChoice | BallotMethod::Stv => prompt_ranking(&options, &position.rules)?,
+                BallotMethod::Approval => prompt_approval(&options, &position.rules)?,
+                BallotMethod::Score => prompt_scores(&options, &position.rules)?,
+            }
+        };
+        ballot.entries.push(BallotEntry { position_idx: position.idx, choice });
+        ballot.review.push((position.title.clone(), label));
+    }
+
+    for (n, question) in questions.iter().enumerate() {
+        println!("\n❓ Question {} of {}: {}", n + 1, questions.len(), question.text);
+        for (i, (_, label)) in question.options.iter().enumerate() { println!(" - {}: {}", i + 1, label); }
+        println!(" - 0: Abstain");
+        let (option_id, label) = loop {
+            let input = read_input("Enter your answer (0 to abstain, 'q' to cancel): ");
+            if input.eq_ignore_ascii_case("q") { return None; }
+            match input.parse::<usize>() {
+                Ok(0) => break (None, "Abstain".to_string()),
+                Ok(i) if i <= question.options.len() => {
+                    let (oid, label) = &question.options[i - 1];
+                    break (Some(*oid), label.clone());
+                }
+                _ => println!("❌ Invalid answer."),
+            }
+        };
+        ballot.answers.push(QuestionAnswer { question_id: question.id, option_id });
+        ballot.review.push((question.text.clone(), label));
+    }
+    Some(ballot)
+}
+
+/// Pick one candidate, or write one in where allowed. `None` means the
+/// voter cancelled the ballot.
+fn prompt_single(options: &[&CandidateRow], allow_write_in: bool) -> Option<(PositionChoice, String)> {
+    if allow_write_in { println!(" - W: Write in a candidate"); }
+    loop {
+        let cand_input = read_input("Enter candidate ID (0 to abstain, 'q' to cancel): ");
+        if cand_input.eq_ignore_ascii_case("q") { return None; }
+        if allow_write_in && cand_input.eq_ignore_ascii_case("w") {
+            let name = read_input("Write-in name: ");
+            if name.is_empty() { println!("❌ A write-in needs a name."); continue; }
+            let label = format!("{} (write-in)", name);
+            return Some((PositionChoice::WriteIn(name), label));
+        }
+        let candidate_id = match cand_input.parse::<i64>() { Ok(v) => v, Err(_) => { println!("❌ Invalid candidate ID"); continue; } };
+        if candidate_id == 0 {
+            return Some((PositionChoice::Abstain, "Abstain".to_string()));
+        }
+        match options.iter().find(|(cid, _, _, _)| *cid == candidate_id) {
+            Some((cid, cname, party, _)) => return Some((PositionChoice::Candidate(*cid), format!("{} ({})", cname, party))),
+            None => println!("❌ Candidate not found for this position."),
+        }
+    }
+}
+
+/// Rank candidates in order of preference within the position's limits.
+/// `None` means the voter cancelled the ballot.
+fn prompt_ranking(options: &[&CandidateRow], rules: &BallotRules) -> Option<(PositionChoice, String)> {
+    println!("Rank the candidates in order of preference, most preferred first.");
+    println!("Rank {}.", limits_text(rules));
+    'prompt: loop {
+        let input = read_input("Enter candidate IDs separated by commas, e.g. 3,1,2 (0 to abstain, 'q' to cancel): ");
+        if input.eq_ignore_ascii_case("q") { return None; }
+        if input == "0" {
+            return Some((PositionChoice::Abstain, "Abstain".to_string()));
+        }
+
+        let mut ranking: Vec<i64> = Vec::new();
+        let mut labels: Vec<String> = Vec::new();
+        for part in input.split(',').map(|p| p.trim()) {
+            let candidate_id = match part.parse::<i64>() { Ok(v) => v, Err(_) => { println!("❌ Invalid candidate ID '{}'", part); continue 'prompt; } };
+            if ranking.contains(&candidate_id) {
+                println!("❌ Candidate {} is ranked more than once.", candidate_id);
+                continue 'prompt;
+            }
+            match options.iter().find(|(cid, _, _, _)| *cid == candidate_id) {
+                Some((cid, cname, _, _)) => {
+                    ranking.push(*cid);
+                    labels.push(format!("{}. {}", ranking.len(), cname));
+                }
+                None => { println!("❌ Candidate {} not found for this position.", candidate_id); continue 'prompt; }
+            }
+        }
+        let choice = PositionChoice::Ranked(ranking);
+        if let Err(e) = validate_choice(rules, &choice) {
+            println!("❌ {}", e);
+            continue;
+        }
+        return Some((choice, labels.join(", ")));
+    }
+}
+
+/// Describe the selection limits of a ranked, approval or score position.
+fn limits_text(rules: &BallotRules) -> String {
+    match (rules.min_selections, rules.max_selections) {
+        (0, 0) => "any number of candidates".to_string(),
+        (min, 0) => format!("at least {} candidates", min),
+        (0, max) => format!("up to {} candidates", max),
+        (min, max) if min == max => format!("exactly {} candidates", min),
+        (min, max) => format!("between {} and {} candidates", min, max),
+    }
+}
+
+/// Approve any number of candidates within the position's limits. `None`
+/// means the voter cancelled the ballot.
+fn prompt_approval(options: &[&CandidateRow], rules: &BallotRules) -> Option<(PositionChoice, String)> {
+    println!("Approve {}.", limits_text(rules));
+    'prompt: loop {
+        let input = read_input("Enter candidate IDs to approve separated by commas (0 to abstain, 'q' to cancel): ");
+        if input.eq_ignore_ascii_case("q") { return None; }
+        if input == "0" {
+            return Some((PositionChoice::Abstain, "Abstain".to_string()));
+        }
+
+        let mut approved: Vec<i64> = Vec::new();
+        let mut labels: Vec<String> = Vec::new();
+        for part in input.split(',').map(|p| p.trim()) {
+            let candidate_id = match part.parse::<i64>() { Ok(v) => v, Err(_) => { println!("❌ Invalid candidate ID '{}'", part); continue 'prompt; } };
+            if approved.contains(&candidate_id) { continue; }
+            match options.iter().find(|(cid, _, _, _)| *cid == candidate_id) {
+                Some((cid, cname, _, _)) => { approved.push(*cid); labels.push(cname.clone()); }
+                None => { println!("❌ Candidate {} not found for this position.", candidate_id); continue 'prompt; }
+            }
+        }
+        let choice = PositionChoice::Approval(approved);
+        if let Err(e) = validate_choice(rules, &choice) {
+            println!("❌ {}", e);
+            continue;
+        }
+        return Some((choice, format!("Approve: {}", labels.join(", "))));
+    }
+}
+
+/// Score every candidate from 0 to the position's maximum. `None` means the
+/// voter cancelled the ballot.
+fn prompt_scores(options: &[&CandidateRow], rules: &BallotRules) -> Option<(PositionChoice, String)> {
+    println!("Score each candidate from 0 to {}; give a non-zero score to {}.", rules.max_score, limits_text(rules));
+    println!("Type 'a' at any prompt to abstain on this position, 'q' to cancel.");
+    'prompt: loop {
+        let mut scores: Vec<(i64, u32)> = Vec::new();
+        let mut labels: Vec<String> = Vec::new();
+        for (cid, cname, party, _) in options {
+            loop {
+                let input = read_input(&format!("Score for {} ({}): ", cname, party));
+                if input.eq_ignore_ascii_case("q") { return None; }
+                if input.eq_ignore_ascii_case("a") {
+                    return Some((PositionChoice::Abstain, "Abstain".to_string()));
+                }
+                match input.parse::<u32>() {
+                    Ok(v) if v <= rules.max_score => {
+                        scores.push((*cid, v));
+                        labels.push(format!("{} = {}", cname, v));
+                        break;
+                    }
+                    _ => println!("❌ Enter a whole number from 0 to {}.", rules.max_score),
+                }
+            }
+        }
+        let choice = PositionChoice::Score(scores);
+        if let Err(e) = validate_choice(rules, &choice) {
+            println!("❌ {}; please score again.", e);
+            continue 'prompt;
+        }
+        return Some((choice, labels.join(", ")));
+    }
+}